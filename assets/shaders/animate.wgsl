@@ -0,0 +1,15 @@
+// Moves the demo triangle's corners on small circles; base_vertex.wgsl adds the offsets.
+// `rest` keeps them in place while `animate` is still compiling.
+@group(0) @binding(0) var<uniform> time: f32;
+@group(0) @binding(1) var<storage, read_write> offsets: array<vec2<f32>, 3>;
+
+@compute @workgroup_size(3)
+fn animate(@builtin(local_invocation_index) corner: u32) {
+    let phase = time * 2.0 + f32(corner) * 2.0944;
+    offsets[corner] = vec2<f32>(cos(phase), sin(phase)) * 0.08;
+}
+
+@compute @workgroup_size(3)
+fn rest(@builtin(local_invocation_index) corner: u32) {
+    offsets[corner] = vec2<f32>(0.0);
+}
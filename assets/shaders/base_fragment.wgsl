@@ -1,8 +1,11 @@
+// scales the vertex colors, set per pipeline variant
+override BRIGHTNESS: f32 = 1.0;
+
 struct FragmentInput {
     @location(0) color: vec3<f32>,
 };
 
 @fragment
 fn main(input: FragmentInput) -> @location(0) vec4<f32> {
-    return vec4<f32>(input.color, 1.0); 
-}
\ No newline at end of file
+    return vec4<f32>(input.color * BRIGHTNESS, 1.0); 
+}
@@ -1,12 +1,37 @@
+struct ViewUniforms {
+    view: mat4x4<f32>,
+    projection: mat4x4<f32>,
+    view_projection: mat4x4<f32>,
+    position: vec4<f32>,
+    viewport: vec4<f32>,
+};
+
 struct VertexOutput {
     @builtin(position) position: vec4<f32>,
     @location(0) color: vec3<f32>,
 };
 
+#ifdef MULTIVIEW
+// one per eye of a multiview pass, indexed by the view being rendered
+@group(0) @binding(0) var<uniform> eyes: array<ViewUniforms, 2>;
+#else
+@group(0) @binding(0) var<uniform> view: ViewUniforms;
+#endif
+// one color per vertex
+@group(1) @binding(0) var palette: texture_2d<f32>;
+// per vertex, written by animate.wgsl
+@group(2) @binding(0) var<storage, read> offsets: array<vec2<f32>, 3>;
+
 @vertex
 fn main(
-    @builtin(vertex_index) vertexIndex: u32
+    @builtin(vertex_index) vertexIndex: u32,
+#ifdef MULTIVIEW
+    @builtin(view_index) viewIndex: u32,
+#endif
 ) -> VertexOutput {
+#ifdef MULTIVIEW
+    let view = eyes[viewIndex];
+#endif
     
     var pos = array<vec2<f32>, 3>(
         vec2<f32>(0.0, 0.5),   // Top center
@@ -15,8 +40,8 @@ fn main(
     );
 
     var out: VertexOutput;
-    out.position = vec4<f32>(pos[vertexIndex], 0.0, 1.0);
-    out.color = vec3<f32>(1.0, 0.0, 0.0);
+    out.position = view.view_projection * vec4<f32>(pos[vertexIndex] + offsets[vertexIndex], 0.0, 1.0);
+    out.color = textureLoad(palette, vec2<i32>(i32(vertexIndex), 0), 0).rgb;
     
     return out;
-}
\ No newline at end of file
+}
@@ -0,0 +1,22 @@
+// Fullscreen gradient behind the demo scene, from one triangle covering the screen.
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) height: f32,
+};
+
+@vertex
+fn vs_sky(@builtin(vertex_index) vertexIndex: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((vertexIndex << 1u) & 2u), f32(vertexIndex & 2u));
+
+    var out: VertexOutput;
+    out.position = vec4<f32>(uv * 2.0 - 1.0, 1.0, 1.0);
+    out.height = uv.y;
+    return out;
+}
+
+@fragment
+fn fs_sky(input: VertexOutput) -> @location(0) vec4<f32> {
+    let horizon = vec3<f32>(0.02, 0.02, 0.03);
+    let zenith = vec3<f32>(0.05, 0.08, 0.2);
+    return vec4<f32>(mix(horizon, zenith, clamp(input.height, 0.0, 1.0)), 1.0);
+}
@@ -0,0 +1,15 @@
+// Side-by-side stereo preview, shown 1:1 in the top-left corner of the scene. Drawn with a
+// viewport covering just the preview, by one triangle covering the viewport.
+@group(0) @binding(0) var preview: texture_2d<f32>;
+
+@vertex
+fn vs_inset(@builtin(vertex_index) vertexIndex: u32) -> @builtin(position) vec4<f32> {
+    let uv = vec2<f32>(f32((vertexIndex << 1u) & 2u), f32(vertexIndex & 2u));
+    return vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+}
+
+@fragment
+fn fs_inset(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    // the viewport starts at the target's origin, so pixels and preview texels line up
+    return textureLoad(preview, vec2<i32>(position.xy), 0);
+}
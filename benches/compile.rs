@@ -0,0 +1,78 @@
+//! Planning an unchanged graph, which reuses the previous plan, against planning it from
+//! scratch. `plan` is the CPU side of `RenderGraph::compile`; physical resources and bind groups
+//! follow the plan and are reused under the same condition.
+
+use criterion::{Criterion, criterion_group, criterion_main};
+use wgpu_engine::gpu::render_graph::graph::RenderGraph;
+use wgpu_engine::gpu::render_graph::types::{NodeType, TextureDesc};
+
+// chains of passes, each downsampling the previous one's target, combined at the end
+const CHAINS: u32 = 8;
+const PASSES_PER_CHAIN: u32 = 16;
+
+fn build_frame(graph: &mut RenderGraph) {
+    let target = |graph: &mut RenderGraph, name: &str, width: u32| {
+        graph.add_texture(
+            name,
+            TextureDesc {
+                size: wgpu::Extent3d { width, height: width, depth_or_array_layers: 1 },
+                dimension: wgpu::TextureDimension::D2,
+                format: wgpu::TextureFormat::Rgba16Float,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            },
+        )
+    };
+
+    let output = target(graph, "Output", 1024);
+    graph.mark_output(output);
+
+    let mut chain_outputs = Vec::new();
+    for chain in 0..CHAINS {
+        let mut previous = None;
+        for pass in 0..PASSES_PER_CHAIN {
+            let name = format!("Chain {chain} pass {pass}");
+            let written = target(graph, &name, 1024 >> (pass % 8));
+            let mut builder = graph.add_pass(&name, NodeType::RenderPass).write(written);
+            if let Some(previous) = previous {
+                builder = builder.read(previous);
+            }
+            builder.execute(|_| {});
+            previous = Some(written);
+        }
+        chain_outputs.extend(previous);
+    }
+    let mut combine = graph.add_pass("Combine", NodeType::RenderPass).write(output);
+    for input in chain_outputs {
+        combine = combine.read(input);
+    }
+    combine.execute(|_| {});
+}
+
+fn plan(c: &mut Criterion) {
+    let mut group = c.benchmark_group("plan");
+
+    group.bench_function("cold", |b| {
+        b.iter(|| {
+            let mut graph = RenderGraph::new();
+            build_frame(&mut graph);
+            graph.plan().expect("graph should plan");
+            graph
+        })
+    });
+
+    let mut graph = RenderGraph::new();
+    build_frame(&mut graph);
+    graph.plan().expect("graph should plan");
+    group.bench_function("unchanged", |b| {
+        b.iter(|| {
+            graph.begin_frame();
+            build_frame(&mut graph);
+            graph.plan().expect("graph should plan");
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, plan);
+criterion_main!(benches);
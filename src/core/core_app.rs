@@ -1,21 +1,40 @@
+use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Instant;
 
 use wgpu::Device;
 use winit::{
     application::ApplicationHandler,
-    event::{DeviceEvent, KeyEvent, WindowEvent},
+    event::{DeviceEvent, ElementState, KeyEvent, WindowEvent},
     event_loop::ActiveEventLoop,
+    keyboard::{KeyCode, PhysicalKey},
     window::{Window, WindowId},
 };
 
-use crate::{gpu::render_graph::{graph::RenderGraph, resource_pool::Resources}, user_app};
-use crate::{core::user_app::UserApp, gpu::context::WgpuCtx};
+use crate::{gpu::render_graph::{feature::{FrameInputs, RenderFeatures, SCENE_COLOR}, graph::RenderGraph, resource_pool::Resources}, user_app};
+use crate::{core::user_app::UserApp, gpu::{context::WgpuCtx, profiler::Profiler, upscale::Upscaler}};
+use crate::core::dynamic_resolution::{DynamicResolution, DynamicResolutionSettings};
+
+// frames captured into a trace when F9 is pressed
+const TRACE_FRAMES: u32 = 60;
+
 pub struct CoreApp<'window, T: UserApp> {
     window: Option<Arc<Window>>,
     wgpu_ctx: Option<WgpuCtx<'window>>,
     resources: Option<Resources>,
     user_app: Option<T>,
+    features: RenderFeatures,
+    // kept across frames so the compiled schedule can be reused
+    render_graph: RenderGraph,
+    profiler: Option<Profiler>,
+    dynamic_resolution: DynamicResolution,
+    upscaler: Option<Upscaler>,
+    // set by F10, writes the next compiled plan to a file
+    dump_graph: bool,
     exit_requested: bool,
+
+    frame_index: u64,
+    last_frame: Option<Instant>,
 }
 
 impl<'window, T: UserApp> CoreApp<'window, T> {
@@ -27,8 +46,17 @@ impl<'window, T: UserApp> CoreApp<'window, T> {
             resources: None,
 
             user_app: None,
+            features: RenderFeatures::new(),
+            render_graph: RenderGraph::new(),
+            profiler: None,
+            dynamic_resolution: DynamicResolution::new(DynamicResolutionSettings::default()),
+            upscaler: None,
+            dump_graph: false,
 
             exit_requested: false,
+
+            frame_index: 0,
+            last_frame: None,
         }
     }
 }
@@ -39,13 +67,24 @@ impl<'window, T: UserApp> ApplicationHandler for CoreApp<'window, T> {
         if self.window.is_none() {
             let window = create_window(event_loop);
             let wgpu_ctx = WgpuCtx::new(Arc::clone(&window));
-            let resources = Resources::new(Arc::clone(&wgpu_ctx.device));
+            let mut resources = Resources::new(Arc::clone(&wgpu_ctx.device));
+            // before `init`, so the app's pipelines are cached too
+            resources.enable_pipeline_cache(&wgpu_ctx.adapter_info(), &pipeline_cache_dir());
+            let profiler = Profiler::new(&wgpu_ctx.device, &wgpu_ctx.queue);
+            let upscaler = Upscaler::new(&wgpu_ctx.device, wgpu_ctx.surface_format());
+
+            let mut user_app = T::init(&mut resources);
+            user_app.register_features(&mut self.features);
+            self.features.setup(&mut resources);
 
             window.request_redraw();
 
             self.window = Some(window);
             self.wgpu_ctx = Some(wgpu_ctx);
             self.resources = Some(resources);
+            self.user_app = Some(user_app);
+            self.profiler = Some(profiler);
+            self.upscaler = Some(upscaler);
         }
     }
 
@@ -59,10 +98,49 @@ impl<'window, T: UserApp> ApplicationHandler for CoreApp<'window, T> {
             WindowEvent::CloseRequested => {
                 event_loop.exit();
             }
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        physical_key: PhysicalKey::Code(KeyCode::F9),
+                        state: ElementState::Pressed,
+                        repeat: false,
+                        ..
+                    },
+                ..
+            } => {
+                if let Some(profiler) = self.profiler.as_mut() {
+                    profiler.capture(TRACE_FRAMES);
+                }
+            }
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        physical_key: PhysicalKey::Code(KeyCode::F10),
+                        state: ElementState::Pressed,
+                        repeat: false,
+                        ..
+                    },
+                ..
+            } => {
+                self.dump_graph = true;
+            }
+            WindowEvent::Resized(size) => {
+                if let (Some(ctx), Some(resources)) = (&mut self.wgpu_ctx, &mut self.resources) {
+                    ctx.resize(size.width, size.height);
+                    let (width, height) = ctx.surface_size();
+                    self.features.resize(resources, width, height);
+                }
+            }
             // fixed for now, need draw to get the window on screen
             WindowEvent::RedrawRequested => {
                 // This is where your Render Graph logic will eventually live
-                if let (Some(ctx), Some(user_app)) = (&self.wgpu_ctx, &mut self.user_app) {
+                if let (Some(ctx), Some(user_app), Some(resources), Some(profiler), Some(upscaler)) = (
+                    &self.wgpu_ctx,
+                    &mut self.user_app,
+                    &mut self.resources,
+                    &mut self.profiler,
+                    &self.upscaler,
+                ) {
                     // 1. Get the current frame from the swapchain
                     let frame = ctx
                         .surface
@@ -72,17 +150,80 @@ impl<'window, T: UserApp> ApplicationHandler for CoreApp<'window, T> {
                         .texture
                         .create_view(&wgpu::TextureViewDescriptor::default());
 
-                    let mut render_graph = RenderGraph::new();
+                    let now = Instant::now();
+                    let delta_time = self
+                        .last_frame
+                        .map(|last| now.duration_since(last).as_secs_f32())
+                        .unwrap_or(0.0);
+                    self.last_frame = Some(now);
+
+                    // frames whose GPU time has not arrived yet are skipped, not guessed
+                    if let Some(gpu_frame_time) = profiler.gpu_frame_time() {
+                        self.dynamic_resolution.update(gpu_frame_time);
+                    }
+
+                    let (width, height) = ctx.surface_size();
+                    let (render_width, render_height) = self.dynamic_resolution.render_size(width, height);
+                    let frame_inputs = FrameInputs {
+                        delta_time,
+                        render_width,
+                        render_height,
+                    };
+                    profiler.begin_frame(self.frame_index);
+                    self.frame_index += 1;
+
+                    let start = Instant::now();
+                    resources.finish_pending_pipelines();
+                    resources.reload_changed_shaders();
+                    profiler.record("Shader reload", start);
 
-                    user_app.update(&mut render_graph);
+                    let render_graph = &mut self.render_graph;
+                    render_graph.begin_frame();
+
+                    let start = Instant::now();
+                    self.features.build(render_graph, &frame_inputs);
+                    profiler.record("Features", start);
+
+                    let start = Instant::now();
+                    user_app.update(render_graph);
+                    profiler.record("Update", start);
+
+                    let start = Instant::now();
+                    let compiled = render_graph.compile(&ctx.device, resources, profiler);
+                    let stats = render_graph.compile_stats();
+                    let span = if stats.cache_hit { "Compile (cached)" } else { "Compile" };
+                    profiler.record_between(span, "cpu", start, start + stats.duration);
+
+                    let graph_commands = match compiled {
+                        Ok(commands) => {
+                            if std::mem::take(&mut self.dump_graph) {
+                                let path = PathBuf::from(format!("graph_frame{}.json", self.frame_index));
+                                match render_graph.write_plan(&path, resources.transient_pool().stats()) {
+                                    Ok(()) => println!("Wrote render graph to {}", path.display()),
+                                    Err(err) => eprintln!("Failed to write render graph {}: {err}", path.display()),
+                                }
+                            }
+                            Some(commands)
+                        }
+                        Err(err) => {
+                            eprintln!("Render graph compilation failed: {err}");
+                            None
+                        }
+                    };
 
                     // 2. Create a command encoder
                     let mut encoder = ctx
                         .device
                         .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
 
-                    // 3. Just clear the screen to a color (this counts as "drawing")
-                    {
+                    // 3. Upscale the graph's output to the window, or just clear it
+                    let scene_color = graph_commands
+                        .as_ref()
+                        .and(render_graph.blackboard().get(SCENE_COLOR))
+                        .and_then(|resource| render_graph.texture_view(resource));
+                    if let Some(source) = scene_color {
+                        upscaler.record(&ctx.device, &mut encoder, source, &view);
+                    } else {
                         let _rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
                             label: Some("Clear Pass"),
                             color_attachments: &[Some(wgpu::RenderPassColorAttachment {
@@ -102,8 +243,21 @@ impl<'window, T: UserApp> ApplicationHandler for CoreApp<'window, T> {
                     }
 
                     // 4. Submit and Present
-                    ctx.queue.submit(Some(encoder.finish()));
+                    let submitted = Instant::now();
+                    ctx.queue.submit(graph_commands.into_iter().chain(Some(encoder.finish())));
+                    profiler.record("Submit", submitted);
+
+                    let start = Instant::now();
                     frame.present();
+                    profiler.record("Present", start);
+
+                    if profiler.end_frame(&ctx.device, submitted) {
+                        let path = PathBuf::from(format!("trace_frame{}.json", self.frame_index));
+                        match profiler.write_trace(&path) {
+                            Ok(()) => println!("Wrote trace to {}", path.display()),
+                            Err(err) => eprintln!("Failed to write trace {}: {err}", path.display()),
+                        }
+                    }
                 }
             }
             _ => (),
@@ -118,6 +272,15 @@ impl<'window, T: UserApp> ApplicationHandler for CoreApp<'window, T> {
     ) {
     }
 
+    fn exiting(&mut self, _event_loop: &ActiveEventLoop) {
+        if let Some(resources) = self.resources.as_mut() {
+            self.features.teardown(resources);
+            if let Err(err) = resources.save_pipeline_cache() {
+                eprintln!("Failed to save the pipeline cache: {err}");
+            }
+        }
+    }
+
     // emitted after one update
     fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
         if self.exit_requested {
@@ -130,6 +293,15 @@ impl<'window, T: UserApp> ApplicationHandler for CoreApp<'window, T> {
     }
 }
 
+// next to the executable, like the assets
+fn pipeline_cache_dir() -> PathBuf {
+    let mut path = std::env::current_exe().unwrap_or_default();
+    path.pop();
+    path.push("cache");
+    path.push("pipelines");
+    path
+}
+
 fn create_window(event_loop: &ActiveEventLoop) -> Arc<Window> {
     let win_attr = Window::default_attributes()
         .with_title("wGPU Engine")
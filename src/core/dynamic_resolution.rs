@@ -0,0 +1,135 @@
+/// Bounds and tuning of `DynamicResolution`. Times are in milliseconds.
+#[derive(Clone, Copy, Debug)]
+pub struct DynamicResolutionSettings {
+    pub target_frame_time: f32,
+    pub min_scale: f32,
+    pub max_scale: f32,
+    // scales are rounded to multiples of this, so small changes don't resize targets
+    pub step: f32,
+    // the scale only grows again below `target * (1 - headroom)`
+    pub headroom: f32,
+    // weight of the newest sample in the frame time average
+    pub smoothing: f32,
+    // frames to wait after a change before measuring its effect
+    pub cooldown_frames: u32,
+}
+
+impl Default for DynamicResolutionSettings {
+    fn default() -> Self {
+        DynamicResolutionSettings {
+            target_frame_time: 1000.0 / 60.0,
+            min_scale: 0.5,
+            max_scale: 1.0,
+            step: 0.05,
+            headroom: 0.15,
+            smoothing: 0.1,
+            cooldown_frames: 30,
+        }
+    }
+}
+
+/// Picks the internal render scale from measured frame times. The decision depends only on
+/// the settings and the samples fed in, so a recorded timing trace replays identically.
+pub struct DynamicResolution {
+    settings: DynamicResolutionSettings,
+    scale: f32,
+    average: Option<f32>,
+    cooldown: u32,
+}
+
+impl DynamicResolution {
+    pub fn new(settings: DynamicResolutionSettings) -> Self {
+        DynamicResolution {
+            settings,
+            scale: settings.max_scale,
+            average: None,
+            cooldown: 0,
+        }
+    }
+
+    /// Feeds the time of the last frame and returns the scale for the next one.
+    pub fn update(&mut self, frame_time: f32) -> f32 {
+        let settings = &self.settings;
+        let average = match self.average {
+            Some(average) => average + (frame_time - average) * settings.smoothing,
+            None => frame_time,
+        };
+        self.average = Some(average);
+
+        if self.cooldown > 0 {
+            self.cooldown -= 1;
+            return self.scale;
+        }
+
+        let target = settings.target_frame_time;
+        let over_budget = average > target;
+        let under_budget = average < target * (1.0 - settings.headroom);
+        if !(over_budget || under_budget) || average <= 0.0 {
+            return self.scale;
+        }
+
+        // frame cost grows with the pixel count, i.e. with the square of the scale; aim for
+        // the middle of the headroom band
+        let aim = target * (1.0 - settings.headroom * 0.5);
+        let desired = self.scale * (aim / average).sqrt();
+        let stepped = (desired / settings.step).floor() * settings.step;
+        let scale = stepped.clamp(settings.min_scale, settings.max_scale);
+
+        if scale != self.scale {
+            self.scale = scale;
+            self.cooldown = settings.cooldown_frames;
+            // samples taken at the old scale say nothing about the new one
+            self.average = None;
+        }
+        self.scale
+    }
+
+    /// `width` x `height` at the current scale, at least one pixel each.
+    pub fn render_size(&self, width: u32, height: u32) -> (u32, u32) {
+        let scaled = |size: u32| ((size as f32 * self.scale).round() as u32).max(1);
+        (scaled(width), scaled(height))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Scales a fresh controller picks for each frame time of `trace`.
+    fn replay(trace: &[f32]) -> Vec<f32> {
+        let mut controller = DynamicResolution::new(DynamicResolutionSettings::default());
+        trace.iter().map(|&frame_time| controller.update(frame_time)).collect()
+    }
+
+    // Feeds `frames` frames whose GPU time is `cost * scale^2`, like a fill-rate bound frame.
+    fn run(controller: &mut DynamicResolution, cost: f32, frames: usize) -> f32 {
+        let mut scale = controller.scale;
+        for _ in 0..frames {
+            scale = controller.update(cost * scale * scale);
+        }
+        scale
+    }
+
+    #[test]
+    fn replays_identically() {
+        let trace: Vec<f32> = (0..300).map(|frame| 12.0 + (frame % 17) as f32 * 0.9).collect();
+        assert_eq!(replay(&trace), replay(&trace));
+    }
+
+    #[test]
+    fn converges_inside_the_budget() {
+        let mut controller = DynamicResolution::new(DynamicResolutionSettings::default());
+        let scale = run(&mut controller, 30.0, 300);
+        assert!((scale - 0.7).abs() < 1e-4, "settled at {scale}");
+
+        // and stays there
+        assert_eq!(run(&mut controller, 30.0, 300), scale);
+    }
+
+    #[test]
+    fn recovers_full_resolution_once_the_load_drops() {
+        let mut controller = DynamicResolution::new(DynamicResolutionSettings::default());
+        assert!(run(&mut controller, 30.0, 300) < 1.0);
+        assert_eq!(run(&mut controller, 8.0, 300), 1.0);
+    }
+}
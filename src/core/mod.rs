@@ -1,2 +1,3 @@
 pub mod core_app;
+pub mod dynamic_resolution;
 pub mod user_app;
@@ -1,8 +1,11 @@
-use crate::gpu::render_graph::{self, graph::RenderGraph, resource_pool::Resources};
+use crate::gpu::render_graph::{self, feature::RenderFeatures, graph::RenderGraph, resource_pool::Resources};
 
 pub trait UserApp {
     fn init(resources: &mut Resources) -> Self;
 
+    // called once after init; features are built into the graph before `update`
+    fn register_features(&mut self, _features: &mut RenderFeatures) {}
+
     fn update(&mut self, render_graph: &mut RenderGraph);
     fn render();
     fn record();
@@ -33,7 +33,12 @@ impl<'window> WgpuCtx<'window> {
         let (device, queue) = adapter
             .request_device(&DeviceDescriptor {
                 label: Some("Device"),
-                required_features: Features::empty(),
+                // optional features the render graph uses when present
+                required_features: adapter.features()
+                    & (Features::CLEAR_TEXTURE
+                        | Features::TIMESTAMP_QUERY
+                        | Features::MULTIVIEW
+                        | Features::PIPELINE_CACHE),
                 required_limits: adapter.limits(),
                 experimental_features: unsafe { ExperimentalFeatures::enabled() },
                 memory_hints: wgpu::MemoryHints::Performance,
@@ -61,4 +66,34 @@ impl<'window> WgpuCtx<'window> {
             queue,
         }
     }
+
+    pub fn resize(&mut self, width: u32, height: u32) {
+        self.surface_config.width = width.max(1);
+        self.surface_config.height = height.max(1);
+        self.surface.configure(&self.device, &self.surface_config);
+    }
+
+    pub fn surface_format(&self) -> wgpu::TextureFormat {
+        self.surface_config.format
+    }
+
+    pub fn surface_size(&self) -> (u32, u32) {
+        (self.surface_config.width, self.surface_config.height)
+    }
+
+    pub fn adapter_info(&self) -> wgpu::AdapterInfo {
+        self.adapter.get_info()
+    }
+}
+
+/// Device on any adapter the tests can get. Tests using it are `#[ignore]`d, run them with
+/// `cargo test -- --include-ignored` where there is an adapter.
+#[cfg(test)]
+pub fn test_device() -> (wgpu::Device, wgpu::Queue) {
+    let instance = wgpu::Instance::default();
+    let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions::default()))
+        .expect("GPU tests need an adapter");
+
+    pollster::block_on(adapter.request_device(&DeviceDescriptor { label: Some("Test device"), ..Default::default() }))
+        .expect("GPU tests need a device")
 }
@@ -1,2 +1,4 @@
 pub mod context;
+pub mod profiler;
 pub mod render_graph;
+pub mod upscale;
@@ -0,0 +1,347 @@
+use std::path::Path;
+use std::sync::mpsc;
+use std::time::Instant;
+
+use serde::Serialize;
+use serde_json::json;
+
+// passes with GPU timestamps per frame; later passes get CPU spans only
+const MAX_TIMED_PASSES: u32 = 256;
+// frames whose timestamps can be on their way back at once; a frame finding every readback
+// buffer still in use goes untimed
+const READBACK_SLOTS: usize = 3;
+
+const CPU_TRACK: u32 = 0;
+const GPU_TRACK: u32 = 1;
+
+/// Records CPU spans and GPU pass timestamps for a range of frames and writes them as Chrome
+/// trace-event JSON (chrome://tracing, Perfetto). GPU timestamps are taken every frame and read
+/// back without waiting for the GPU, for `gpu_frame_time`; everything else only happens while
+/// a capture is running.
+pub struct Profiler {
+    epoch: Instant,
+    frame_index: u64,
+    frames_left: u32,
+    events: Vec<TraceEvent>,
+    // None without `Features::TIMESTAMP_QUERY`
+    gpu: Option<GpuTimer>,
+    // first pass start to last pass end of the newest frame read back by the last
+    // `end_frame`, in milliseconds
+    gpu_frame_time: Option<f32>,
+}
+
+impl Profiler {
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue) -> Self {
+        let gpu = device
+            .features()
+            .contains(wgpu::Features::TIMESTAMP_QUERY)
+            .then(|| GpuTimer::new(device, queue));
+
+        Profiler {
+            epoch: Instant::now(),
+            frame_index: 0,
+            frames_left: 0,
+            events: Vec::new(),
+            gpu,
+            gpu_frame_time: None,
+        }
+    }
+
+    /// Starts capturing the next `frames` frames, dropping anything captured before.
+    pub fn capture(&mut self, frames: u32) {
+        self.events.clear();
+        self.frames_left = frames;
+    }
+
+    pub fn is_capturing(&self) -> bool {
+        self.frames_left > 0
+    }
+
+    pub fn begin_frame(&mut self, frame_index: u64) {
+        self.frame_index = frame_index;
+        self.gpu_frame_time = None;
+    }
+
+    /// GPU time of the newest frame whose timestamps arrived during the last `end_frame`. None
+    /// when none did (results lag a frame or two behind) or without timestamp support.
+    pub fn gpu_frame_time(&self) -> Option<f32> {
+        self.gpu_frame_time
+    }
+
+    /// Records a CPU span from `start` to now.
+    pub fn record(&mut self, name: &str, start: Instant) {
+        self.record_between(name, "cpu", start, Instant::now());
+    }
+
+    pub fn record_between(&mut self, name: &str, category: &'static str, start: Instant, end: Instant) {
+        if !self.is_capturing() {
+            return;
+        }
+
+        let ts = start.duration_since(self.epoch).as_secs_f64() * 1e6;
+        let dur = end.duration_since(start).as_secs_f64() * 1e6;
+        self.push_event(name, category, CPU_TRACK, ts, dur, self.frame_index);
+    }
+
+    /// Reserves a begin/end query pair for a pass named `label`, if GPU timing is available.
+    pub fn pass_timestamps(&mut self, label: &str) -> Option<(u32, u32)> {
+        self.gpu.as_mut()?.allocate(label)
+    }
+
+    pub fn query_set(&self) -> Option<&wgpu::QuerySet> {
+        self.gpu.as_ref().map(|gpu| &gpu.query_set)
+    }
+
+    /// Copies this frame's queries into a readback buffer; the last command in the frame.
+    pub fn resolve(&mut self, encoder: &mut wgpu::CommandEncoder) {
+        if let Some(gpu) = self.gpu.as_mut() {
+            gpu.resolve(encoder);
+        }
+    }
+
+    /// Starts reading back the GPU timestamps of the submitted frame and picks up those of
+    /// earlier frames that arrived, without waiting for the GPU, except when a capture finishes:
+    /// then every frame still in flight is waited for. Captured GPU times are placed on their
+    /// own track, starting at their frame's `submitted`. Returns whether the capture just
+    /// finished.
+    pub fn end_frame(&mut self, device: &wgpu::Device, submitted: Instant) -> bool {
+        let captured = self.is_capturing();
+        if captured {
+            self.frames_left -= 1;
+        }
+        let finished = captured && self.frames_left == 0;
+
+        let Some(gpu) = self.gpu.as_mut() else { return finished; };
+        gpu.submit(TimedFrame {
+            frame_index: self.frame_index,
+            captured,
+            submitted,
+        });
+
+        for (frame, passes) in gpu.collect(device, finished) {
+            // pass times are relative to the first pass start
+            self.gpu_frame_time = passes
+                .iter()
+                .map(|&(_, _, end)| end)
+                .reduce(f64::max)
+                .map(|end| (end / 1000.0) as f32);
+
+            if frame.captured {
+                let origin = frame.submitted.duration_since(self.epoch).as_secs_f64() * 1e6;
+                for (label, start, end) in passes {
+                    self.push_event(&label, "gpu", GPU_TRACK, origin + start, end - start, frame.frame_index);
+                }
+            }
+        }
+
+        finished
+    }
+
+    /// Writes everything captured so far as a Chrome trace-event JSON file.
+    pub fn write_trace(&self, path: &Path) -> std::io::Result<()> {
+        let thread_name = |tid: u32, name: &str| {
+            json!({ "name": "thread_name", "ph": "M", "pid": 0, "tid": tid, "args": { "name": name } })
+        };
+
+        let mut events = vec![thread_name(CPU_TRACK, "CPU"), thread_name(GPU_TRACK, "GPU")];
+        events.extend(self.events.iter().map(|event| json!(event)));
+
+        let trace = json!({ "traceEvents": events, "displayTimeUnit": "ms" });
+        std::fs::write(path, serde_json::to_vec_pretty(&trace)?)
+    }
+
+    fn push_event(&mut self, name: &str, category: &'static str, tid: u32, ts: f64, dur: f64, frame: u64) {
+        self.events.push(TraceEvent {
+            name: name.into(),
+            cat: category,
+            ph: "X",
+            ts,
+            dur,
+            pid: 0,
+            tid,
+            args: json!({ "frame": frame }),
+        });
+    }
+}
+
+// Complete ("X") event of the trace-event format; times in microseconds.
+#[derive(Serialize)]
+struct TraceEvent {
+    name: String,
+    cat: &'static str,
+    ph: &'static str,
+    ts: f64,
+    dur: f64,
+    pid: u32,
+    tid: u32,
+    args: serde_json::Value,
+}
+
+struct GpuTimer {
+    query_set: wgpu::QuerySet,
+    resolve_buffer: wgpu::Buffer,
+    slots: Vec<ReadbackSlot>,
+    // slot the frame being recorded resolves into, picked by its first pass
+    current: Option<usize>,
+    // nanoseconds per timestamp tick
+    period: f64,
+    // pass label per allocated query pair, in query order
+    passes: Vec<String>,
+}
+
+struct ReadbackSlot {
+    buffer: wgpu::Buffer,
+    state: SlotState,
+}
+
+enum SlotState {
+    Free,
+    // resolved by the frame being recorded, not submitted yet
+    Resolved { passes: Vec<String>, queries: u32 },
+    Mapping {
+        frame: TimedFrame,
+        passes: Vec<String>,
+        queries: u32,
+        mapped: mpsc::Receiver<Result<(), wgpu::BufferAsyncError>>,
+    },
+}
+
+// (label, start, end) per pass, in microseconds relative to the first pass
+type PassTimes = Vec<(String, f64, f64)>;
+
+struct TimedFrame {
+    frame_index: u64,
+    captured: bool,
+    submitted: Instant,
+}
+
+impl GpuTimer {
+    fn new(device: &wgpu::Device, queue: &wgpu::Queue) -> Self {
+        let query_count = MAX_TIMED_PASSES * 2;
+        let size = query_count as u64 * wgpu::QUERY_SIZE as u64;
+
+        let slots = (0..READBACK_SLOTS)
+            .map(|slot| ReadbackSlot {
+                buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some(&format!("Profiler/Readback {slot}")),
+                    size,
+                    usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+                    mapped_at_creation: false,
+                }),
+                state: SlotState::Free,
+            })
+            .collect();
+
+        GpuTimer {
+            query_set: device.create_query_set(&wgpu::QuerySetDescriptor {
+                label: Some("Profiler/Timestamps"),
+                ty: wgpu::QueryType::Timestamp,
+                count: query_count,
+            }),
+            resolve_buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Profiler/Resolve"),
+                size,
+                usage: wgpu::BufferUsages::QUERY_RESOLVE | wgpu::BufferUsages::COPY_SRC,
+                mapped_at_creation: false,
+            }),
+            slots,
+            current: None,
+            period: queue.get_timestamp_period() as f64,
+            passes: Vec::new(),
+        }
+    }
+
+    fn allocate(&mut self, label: &str) -> Option<(u32, u32)> {
+        if self.current.is_none() {
+            self.current = self.slots.iter().position(|slot| matches!(slot.state, SlotState::Free));
+        }
+        let pair = self.passes.len() as u32;
+        if self.current.is_none() || pair >= MAX_TIMED_PASSES {
+            return None;
+        }
+
+        self.passes.push(label.into());
+        Some((pair * 2, pair * 2 + 1))
+    }
+
+    fn resolve(&mut self, encoder: &mut wgpu::CommandEncoder) {
+        let Some(slot) = self.current else { return; };
+        let queries = self.passes.len() as u32 * 2;
+
+        let size = queries as u64 * wgpu::QUERY_SIZE as u64;
+        encoder.resolve_query_set(&self.query_set, 0..queries, &self.resolve_buffer, 0);
+        encoder.copy_buffer_to_buffer(&self.resolve_buffer, 0, &self.slots[slot].buffer, 0, size);
+        self.slots[slot].state = SlotState::Resolved {
+            passes: std::mem::take(&mut self.passes),
+            queries,
+        };
+    }
+
+    // Maps the readback buffer resolved by `frame`, once its commands are submitted.
+    fn submit(&mut self, frame: TimedFrame) {
+        // a frame that allocated queries but never resolved them (e.g. a failed compile)
+        self.passes.clear();
+        let Some(slot) = self.current.take() else { return; };
+        let slot = &mut self.slots[slot];
+
+        let SlotState::Resolved { passes, queries } = std::mem::replace(&mut slot.state, SlotState::Free) else {
+            return;
+        };
+        let (sender, mapped) = mpsc::channel();
+        let size = queries as u64 * wgpu::QUERY_SIZE as u64;
+        slot.buffer.slice(..size).map_async(wgpu::MapMode::Read, move |result| {
+            let _ = sender.send(result);
+        });
+        slot.state = SlotState::Mapping { frame, passes, queries, mapped };
+    }
+
+    // Frames whose readback finished, oldest first. Waits for every frame in flight if `wait`.
+    fn collect(&mut self, device: &wgpu::Device, wait: bool) -> Vec<(TimedFrame, PassTimes)> {
+        let poll = match wait {
+            true => wgpu::PollType::wait_indefinitely(),
+            false => wgpu::PollType::Poll,
+        };
+        if device.poll(poll).is_err() {
+            return Vec::new();
+        }
+
+        let mut frames = Vec::new();
+        for slot in self.slots.iter_mut() {
+            let SlotState::Mapping { mapped, .. } = &slot.state else { continue; };
+            let result = match mapped.try_recv() {
+                Ok(result) => result,
+                Err(mpsc::TryRecvError::Empty) => continue,
+                Err(mpsc::TryRecvError::Disconnected) => Err(wgpu::BufferAsyncError),
+            };
+            let SlotState::Mapping { frame, passes, queries, .. } = std::mem::replace(&mut slot.state, SlotState::Free)
+            else {
+                unreachable!("slot was mapping");
+            };
+            if result.is_err() {
+                continue;
+            }
+
+            let size = queries as u64 * wgpu::QUERY_SIZE as u64;
+            let ticks: Vec<u64> = slot
+                .buffer
+                .slice(..size)
+                .get_mapped_range()
+                .chunks_exact(8)
+                .map(|bytes| u64::from_le_bytes(bytes.try_into().expect("8 byte chunk")))
+                .collect();
+            slot.buffer.unmap();
+
+            let first = ticks.iter().step_by(2).copied().min().unwrap_or(0);
+            let to_micros = |tick: u64| tick.saturating_sub(first) as f64 * self.period / 1000.0;
+            let passes = passes
+                .into_iter()
+                .zip(ticks.chunks_exact(2))
+                .map(|(label, pair)| (label, to_micros(pair[0]), to_micros(pair[1].max(pair[0]))))
+                .collect();
+            frames.push((frame, passes));
+        }
+
+        frames.sort_by_key(|(frame, _)| frame.frame_index);
+        frames
+    }
+}
@@ -0,0 +1,32 @@
+use std::collections::HashMap;
+
+use crate::gpu::render_graph::types::ResourceHandle;
+
+/// Named resource handles shared between render features within a frame,
+/// e.g. the shadow feature publishes "shadow_map" and lighting looks it up.
+pub struct Blackboard {
+    entries: HashMap<String, ResourceHandle>,
+}
+
+impl Blackboard {
+    pub fn new() -> Self {
+        Blackboard {
+            entries: HashMap::new(),
+        }
+    }
+
+    /// Publishes `resource` under `name`, replacing any previous entry.
+    pub fn insert(&mut self, name: &str, resource: ResourceHandle) {
+        self.entries.insert(name.into(), resource);
+    }
+
+    pub fn get(&self, name: &str) -> Option<ResourceHandle> {
+        self.entries.get(name).copied()
+    }
+}
+
+impl Default for Blackboard {
+    fn default() -> Self {
+        Self::new()
+    }
+}
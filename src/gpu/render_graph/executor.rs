@@ -0,0 +1,782 @@
+use std::collections::HashMap;
+use std::num::NonZeroU32;
+use std::time::Instant;
+
+use serde::Serialize;
+use slotmap::SecondaryMap;
+use wgpu::wgt::CommandEncoderDescriptor;
+
+use crate::gpu::profiler::Profiler;
+use crate::gpu::render_graph::plan::{ExecutionPlan, PlannedNode, ResourceDesc};
+use crate::gpu::render_graph::resource_pool::Resources;
+use crate::gpu::render_graph::transient_pool::{PooledBuffer, PooledTexture, TransientPool};
+use crate::gpu::render_graph::types::{
+    BufferHandle, ClearTextureOp, Node, NodeType, PassContext, PipelineHandle, ResourceHandle, TextureHandle,
+    TextureRegion, Viewport, padded_bytes_per_row, unpadded_bytes_per_row,
+};
+
+/// Execution phase of `RenderGraph::compile`. Receives the plan's steps in order; `node` is the
+/// graph node a planned node was built from, holding its closure and transfer data.
+pub trait GraphExecutor {
+    /// Called once per frame before any step, with the plan about to run.
+    fn prepare(&mut self, plan: &ExecutionPlan);
+    fn push_debug_group(&mut self, label: &str);
+    fn pop_debug_group(&mut self);
+    fn execute_node(&mut self, plan: &ExecutionPlan, planned: &PlannedNode, node: &mut Node);
+}
+
+/// GPU objects created for a plan, kept by the graph across frames while the plan hash matches.
+#[derive(Default)]
+pub struct GpuState {
+    hash: Option<u64>,
+    // `Resources::pipeline_generation` the bind groups were created against
+    pipeline_generation: u64,
+    physical: PhysicalResources,
+    // per node index, one per bound group; only nodes with a pipeline and bindings have them
+    bind_groups: HashMap<usize, Vec<(u32, wgpu::BindGroup)>>,
+}
+
+impl GpuState {
+    /// View of the physical texture behind `resource`, while the plan using it is current.
+    pub fn texture_view(&self, resource: ResourceHandle) -> Option<&wgpu::TextureView> {
+        let ResourceHandle::Texture(handle) = resource else { return None; };
+        self.physical.textures.get(handle).map(|texture| &texture.view)
+    }
+}
+
+/// Records a plan into a wgpu command encoder. Pass closures are timed by `profiler`, and
+/// passes get GPU timestamps when it has a query set.
+pub struct WgpuExecutor<'a> {
+    device: &'a wgpu::Device,
+    resources: &'a mut Resources,
+    state: &'a mut GpuState,
+    profiler: &'a mut Profiler,
+    encoder: wgpu::CommandEncoder,
+}
+
+impl<'a> WgpuExecutor<'a> {
+    pub fn new(
+        device: &'a wgpu::Device,
+        resources: &'a mut Resources,
+        state: &'a mut GpuState,
+        profiler: &'a mut Profiler,
+    ) -> Self {
+        let encoder = device.create_command_encoder(&CommandEncoderDescriptor {
+            label: Some("RenderGraph"),
+        });
+
+        WgpuExecutor {
+            device,
+            resources,
+            state,
+            profiler,
+            encoder,
+        }
+    }
+
+    pub fn finish(mut self) -> wgpu::CommandBuffer {
+        self.profiler.resolve(&mut self.encoder);
+        self.encoder.finish()
+    }
+
+    // Takes one physical texture / buffer for every resource used by the scheduled nodes from
+    // the transient pool.
+    fn allocate_resources(&mut self, plan: &ExecutionPlan) -> PhysicalResources {
+        let mut physical = PhysicalResources::default();
+        let pool = self.resources.transient_pool_mut();
+
+        for planned in plan.resources.iter() {
+            let label = planned.label.as_str();
+
+            match (planned.resource, &planned.desc) {
+                (ResourceHandle::Texture(handle), ResourceDesc::Texture(desc)) => {
+                    physical.textures.insert(handle, pool.acquire_texture(self.device, desc, label));
+                }
+                (ResourceHandle::Buffer(handle), ResourceDesc::Buffer(desc)) => {
+                    physical.buffers.insert(handle, pool.acquire_buffer(self.device, desc, label));
+                }
+                _ => unreachable!("planned description does not match the resource kind"),
+            }
+        }
+
+        physical
+    }
+
+    // One bind group per group the node binds to, created with the layout its pipeline has for
+    // that group.
+    fn create_bind_groups(&self, planned: &PlannedNode, physical: &PhysicalResources) -> Option<Vec<(u32, wgpu::BindGroup)>> {
+        if planned.bindings.is_empty() {
+            return None;
+        }
+
+        let layout = |group: u32| match planned.pipeline {
+            Some(PipelineHandle::Render(handle)) => {
+                Some(self.resources.render_pipeline_or_fallback(handle)?.get_bind_group_layout(group))
+            }
+            Some(PipelineHandle::Compute(handle)) => {
+                Some(self.resources.compute_pipeline_or_fallback(handle)?.get_bind_group_layout(group))
+            }
+            None => None,
+        };
+
+        planned
+            .bindings
+            .chunk_by(|a, b| a.group == b.group)
+            .map(|bindings| {
+                let group = bindings[0].group;
+                let entries: Vec<wgpu::BindGroupEntry> = bindings
+                    .iter()
+                    .map(|binding| wgpu::BindGroupEntry {
+                        binding: binding.binding,
+                        resource: physical.binding_resource(binding.resource),
+                    })
+                    .collect();
+
+                let bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    label: Some(&format!("{}/Group {group}", planned.label)),
+                    layout: &layout(group)?,
+                    entries: &entries,
+                });
+                Some((group, bind_group))
+            })
+            .collect()
+    }
+
+    // Uploads go through staging buffers copied inside the encoder, so they are ordered with
+    // the passes around them instead of landing before the whole frame like queue writes.
+    fn execute_transfer(&mut self, plan: &ExecutionPlan, planned: &PlannedNode, node: &Node) {
+        let Some(ops) = &node.transfer else { return; };
+        let label = planned.label.as_str();
+        let physical = &self.state.physical;
+        let encoder = &mut self.encoder;
+
+        for op in ops.clear_buffer.iter() {
+            encoder.clear_buffer(physical.buffer(op.target), op.offset, op.size);
+        }
+
+        for op in ops.clear_texture.iter() {
+            clear_texture(op, plan, self.device, physical, encoder);
+        }
+
+        for op in ops.upload.iter() {
+            let staging = create_staging_buffer(self.device, label, &op.data);
+            encoder.copy_buffer_to_buffer(&staging, 0, physical.buffer(op.target), op.offset, op.data.len() as u64);
+        }
+
+        for op in ops.copy.iter() {
+            encoder.copy_buffer_to_buffer(
+                physical.buffer(op.src),
+                op.src_offset,
+                physical.buffer(op.dst),
+                op.dst_offset,
+                op.size,
+            );
+        }
+
+        for op in ops.texture_upload.iter() {
+            let desc = plan.texture_desc(op.target);
+            let region = &op.region;
+            let (_, block_height) = desc.format.block_dimensions();
+
+            let row_bytes = unpadded_bytes_per_row(desc.format, region.aspect, region.extent.width) as usize;
+            let padded_row_bytes = padded_bytes_per_row(desc.format, region.aspect, region.extent.width);
+            let rows = region.extent.height.div_ceil(block_height) as usize;
+            let layers = region.extent.depth_or_array_layers as usize;
+
+            let src_row_pitch = op.layout.bytes_per_row.map_or(row_bytes, |pitch| pitch as usize);
+            let src_image_pitch = op.layout.rows_per_image.map_or(rows, |rows| rows as usize) * src_row_pitch;
+
+            let mut staging_data = vec![0u8; padded_row_bytes as usize * rows * layers];
+            for layer in 0..layers {
+                for row in 0..rows {
+                    let src = op.layout.offset as usize + layer * src_image_pitch + row * src_row_pitch;
+                    let dst = (layer * rows + row) * padded_row_bytes as usize;
+                    staging_data[dst..dst + row_bytes].copy_from_slice(&op.data[src..src + row_bytes]);
+                }
+            }
+
+            let staging = create_staging_buffer(self.device, label, &staging_data);
+            encoder.copy_buffer_to_texture(
+                wgpu::TexelCopyBufferInfo {
+                    buffer: &staging,
+                    layout: wgpu::TexelCopyBufferLayout {
+                        offset: 0,
+                        bytes_per_row: Some(padded_row_bytes),
+                        rows_per_image: Some(rows as u32),
+                    },
+                },
+                physical.texture_copy_info(op.target, region),
+                region.extent,
+            );
+        }
+
+        for op in ops.texture_copy.iter() {
+            let region = &op.region;
+            let buffer_layout = |texture: ResourceHandle| {
+                let desc = plan.texture_desc(texture);
+                let (_, block_height) = desc.format.block_dimensions();
+                wgpu::TexelCopyBufferLayout {
+                    offset: op.buffer_offset,
+                    bytes_per_row: Some(padded_bytes_per_row(desc.format, region.aspect, region.extent.width)),
+                    rows_per_image: Some(region.extent.height.div_ceil(block_height)),
+                }
+            };
+
+            match (op.src, op.dst) {
+                (ResourceHandle::Texture(_), ResourceHandle::Texture(_)) => {
+                    encoder.copy_texture_to_texture(
+                        physical.texture_copy_info(op.src, region),
+                        physical.texture_copy_info(op.dst, &op.dst_region),
+                        region.extent,
+                    );
+                }
+                (ResourceHandle::Buffer(_), ResourceHandle::Texture(_)) => {
+                    encoder.copy_buffer_to_texture(
+                        wgpu::TexelCopyBufferInfo {
+                            buffer: physical.buffer(op.src),
+                            layout: buffer_layout(op.dst),
+                        },
+                        physical.texture_copy_info(op.dst, region),
+                        region.extent,
+                    );
+                }
+                (ResourceHandle::Texture(_), ResourceHandle::Buffer(_)) => {
+                    encoder.copy_texture_to_buffer(
+                        physical.texture_copy_info(op.src, region),
+                        wgpu::TexelCopyBufferInfo {
+                            buffer: physical.buffer(op.dst),
+                            layout: buffer_layout(op.src),
+                        },
+                        region.extent,
+                    );
+                }
+                (ResourceHandle::Buffer(_), ResourceHandle::Buffer(_)) => {
+                    unreachable!("buffer to buffer copies are CopyOps")
+                }
+            }
+        }
+    }
+
+    fn execute_render_pass(&mut self, planned: &PlannedNode, node: &mut Node) {
+        let physical = &self.state.physical;
+
+        // only the first pass touching an attachment clears it; later ones, like other views
+        // sharing the target, draw on top
+        let color_load = |resource| match planned.cleared.contains(&resource) {
+            true => wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+            false => wgpu::LoadOp::Load,
+        };
+        let depth_load = |resource| match planned.cleared.contains(&resource) {
+            true => wgpu::LoadOp::Clear(1.0),
+            false => wgpu::LoadOp::Load,
+        };
+
+        let color_attachments: Vec<_> = planned
+            .color_attachments
+            .iter()
+            .map(|&resource| {
+                Some(wgpu::RenderPassColorAttachment {
+                    view: physical.view(resource),
+                    depth_slice: None,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: color_load(resource),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })
+            })
+            .collect();
+
+        let depth_stencil_attachment = planned.depth_attachment.map(|resource| wgpu::RenderPassDepthStencilAttachment {
+            view: physical.view(resource),
+            depth_ops: Some(wgpu::Operations {
+                load: depth_load(resource),
+                store: wgpu::StoreOp::Store,
+            }),
+            stencil_ops: None,
+        });
+
+        let pipeline = match planned.pipeline {
+            Some(PipelineHandle::Render(handle)) => self.resources.render_pipeline_or_fallback(handle),
+            _ => None,
+        };
+
+        let timestamps = self.profiler.pass_timestamps(&planned.label);
+        let timestamp_writes = timestamps.zip(self.profiler.query_set()).map(|((begin, end), query_set)| {
+            wgpu::RenderPassTimestampWrites {
+                query_set,
+                beginning_of_pass_write_index: Some(begin),
+                end_of_pass_write_index: Some(end),
+            }
+        });
+
+        let mut render_pass = self.encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some(&planned.label),
+            color_attachments: &color_attachments,
+            depth_stencil_attachment,
+            timestamp_writes,
+            occlusion_query_set: None,
+            multiview_mask: planned.multiview_mask,
+        });
+
+        if let Some(pipeline) = pipeline {
+            render_pass.set_pipeline(pipeline);
+        }
+        for (group, bind_group) in self.state.bind_groups.get(&planned.node).into_iter().flatten() {
+            render_pass.set_bind_group(*group, bind_group, &[]);
+        }
+        if let Some(viewport) = planned.viewport {
+            render_pass.set_viewport(
+                viewport.x as f32,
+                viewport.y as f32,
+                viewport.width as f32,
+                viewport.height as f32,
+                0.0,
+                1.0,
+            );
+            render_pass.set_scissor_rect(viewport.x, viewport.y, viewport.width, viewport.height);
+        }
+
+        let Some(ctx) = node.execute.take() else { return; };
+        // still compiling without a fallback; the attachments are loaded and stored as planned
+        if planned.pipeline.is_some() && pipeline.is_none() {
+            return;
+        }
+        let start = Instant::now();
+        ctx(PassContext::Render(&mut render_pass));
+        let end = Instant::now();
+
+        drop(render_pass);
+        self.profiler.record_between(&planned.label, "node", start, end);
+    }
+
+    fn execute_compute_pass(&mut self, planned: &PlannedNode, node: &mut Node) {
+        let pipeline = match planned.pipeline {
+            Some(PipelineHandle::Compute(handle)) => self.resources.compute_pipeline_or_fallback(handle),
+            _ => None,
+        };
+
+        let timestamps = self.profiler.pass_timestamps(&planned.label);
+        let timestamp_writes = timestamps.zip(self.profiler.query_set()).map(|((begin, end), query_set)| {
+            wgpu::ComputePassTimestampWrites {
+                query_set,
+                beginning_of_pass_write_index: Some(begin),
+                end_of_pass_write_index: Some(end),
+            }
+        });
+
+        let mut compute_pass = self.encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some(&planned.label),
+            timestamp_writes,
+        });
+
+        if let Some(pipeline) = pipeline {
+            compute_pass.set_pipeline(pipeline);
+        }
+        for (group, bind_group) in self.state.bind_groups.get(&planned.node).into_iter().flatten() {
+            compute_pass.set_bind_group(*group, bind_group, &[]);
+        }
+
+        let Some(ctx) = node.execute.take() else { return; };
+        if planned.pipeline.is_some() && pipeline.is_none() {
+            return;
+        }
+        let start = Instant::now();
+        ctx(PassContext::Compute(&mut compute_pass));
+        let end = Instant::now();
+
+        drop(compute_pass);
+        self.profiler.record_between(&planned.label, "node", start, end);
+    }
+}
+
+impl GraphExecutor for WgpuExecutor<'_> {
+    fn prepare(&mut self, plan: &ExecutionPlan) {
+        let pipeline_generation = self.resources.pipeline_generation();
+        if self.state.hash == Some(plan.hash) {
+            // reloaded pipelines may have new bind group layouts; the resources still fit
+            if self.state.pipeline_generation != pipeline_generation {
+                self.state.bind_groups = plan
+                    .nodes()
+                    .filter_map(|planned| Some((planned.node, self.create_bind_groups(planned, &self.state.physical)?)))
+                    .collect();
+                self.state.pipeline_generation = pipeline_generation;
+            }
+            return;
+        }
+
+        // hand the old resources back first, so same-desc ones are reused instead of created
+        let previous = std::mem::take(self.state);
+        previous.physical.release(self.resources.transient_pool_mut());
+
+        let physical = self.allocate_resources(plan);
+        let bind_groups = plan
+            .nodes()
+            .filter_map(|planned| Some((planned.node, self.create_bind_groups(planned, &physical)?)))
+            .collect();
+
+        *self.state = GpuState {
+            hash: Some(plan.hash),
+            pipeline_generation,
+            physical,
+            bind_groups,
+        };
+    }
+
+    fn push_debug_group(&mut self, label: &str) {
+        self.encoder.push_debug_group(label);
+    }
+
+    fn pop_debug_group(&mut self) {
+        self.encoder.pop_debug_group();
+    }
+
+    fn execute_node(&mut self, plan: &ExecutionPlan, planned: &PlannedNode, node: &mut Node) {
+        match planned.kind {
+            NodeType::RenderPass => self.execute_render_pass(planned, node),
+            NodeType::ComputePass => self.execute_compute_pass(planned, node),
+            NodeType::Transfer => self.execute_transfer(plan, planned, node),
+        }
+    }
+}
+
+/// Command logged by `RecordingExecutor`. Resources are referred to by label.
+#[derive(Serialize, Clone, PartialEq, Eq, Debug)]
+pub enum RecordedCommand {
+    Allocate { resource: String, allocation: usize },
+    PushDebugGroup(String),
+    PopDebugGroup,
+    BeginRenderPass {
+        label: String,
+        color_attachments: Vec<String>,
+        depth_attachment: Option<String>,
+        cleared: Vec<String>,
+        multiview_mask: Option<NonZeroU32>,
+    },
+    BeginComputePass { label: String },
+    SetPipeline(PipelineHandle),
+    SetBindGroup { group: u32, bindings: Vec<(u32, String)> },
+    SetViewport(Viewport),
+    EndPass,
+    ClearBuffer { target: String, offset: u64, size: Option<u64> },
+    ClearTexture { target: String },
+    WriteBuffer { target: String, offset: u64, size: u64 },
+    CopyBuffer { src: String, dst: String, size: u64 },
+    WriteTexture { target: String, mip_level: u32, array_layer: u32 },
+    CopyTexture { src: String, dst: String, mip_level: u32 },
+}
+
+/// Executor that only logs what `WgpuExecutor` would record, so graphs can be checked without
+/// a device. Pass closures need a live pass and are dropped without running.
+#[derive(Default)]
+pub struct RecordingExecutor {
+    hash: Option<u64>,
+    commands: Vec<RecordedCommand>,
+}
+
+impl RecordingExecutor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn commands(&self) -> &[RecordedCommand] {
+        &self.commands
+    }
+
+    fn record_transfer(&mut self, plan: &ExecutionPlan, node: &Node) {
+        let Some(ops) = &node.transfer else { return; };
+        let label = |resource| plan.label(resource).to_string();
+
+        for op in ops.clear_buffer.iter() {
+            self.commands.push(RecordedCommand::ClearBuffer {
+                target: label(op.target),
+                offset: op.offset,
+                size: op.size,
+            });
+        }
+        for op in ops.clear_texture.iter() {
+            self.commands.push(RecordedCommand::ClearTexture { target: label(op.target) });
+        }
+        for op in ops.upload.iter() {
+            self.commands.push(RecordedCommand::WriteBuffer {
+                target: label(op.target),
+                offset: op.offset,
+                size: op.data.len() as u64,
+            });
+        }
+        for op in ops.copy.iter() {
+            self.commands.push(RecordedCommand::CopyBuffer {
+                src: label(op.src),
+                dst: label(op.dst),
+                size: op.size,
+            });
+        }
+        for op in ops.texture_upload.iter() {
+            self.commands.push(RecordedCommand::WriteTexture {
+                target: label(op.target),
+                mip_level: op.region.mip_level,
+                array_layer: op.region.array_layer,
+            });
+        }
+        for op in ops.texture_copy.iter() {
+            self.commands.push(RecordedCommand::CopyTexture {
+                src: label(op.src),
+                dst: label(op.dst),
+                mip_level: op.region.mip_level,
+            });
+        }
+    }
+}
+
+impl GraphExecutor for RecordingExecutor {
+    fn prepare(&mut self, plan: &ExecutionPlan) {
+        if self.hash == Some(plan.hash) {
+            return;
+        }
+        self.hash = Some(plan.hash);
+
+        for planned in plan.resources.iter() {
+            self.commands.push(RecordedCommand::Allocate {
+                resource: planned.label.clone(),
+                allocation: planned.allocation,
+            });
+        }
+    }
+
+    fn push_debug_group(&mut self, label: &str) {
+        self.commands.push(RecordedCommand::PushDebugGroup(label.into()));
+    }
+
+    fn pop_debug_group(&mut self) {
+        self.commands.push(RecordedCommand::PopDebugGroup);
+    }
+
+    fn execute_node(&mut self, plan: &ExecutionPlan, planned: &PlannedNode, node: &mut Node) {
+        let label = |resource| plan.label(resource).to_string();
+
+        match planned.kind {
+            NodeType::RenderPass => self.commands.push(RecordedCommand::BeginRenderPass {
+                label: planned.label.clone(),
+                color_attachments: planned.color_attachments.iter().map(|&resource| label(resource)).collect(),
+                depth_attachment: planned.depth_attachment.map(label),
+                cleared: planned.cleared.iter().map(|&resource| label(resource)).collect(),
+                multiview_mask: planned.multiview_mask,
+            }),
+            NodeType::ComputePass => self.commands.push(RecordedCommand::BeginComputePass {
+                label: planned.label.clone(),
+            }),
+            NodeType::Transfer => {
+                self.record_transfer(plan, node);
+                return;
+            }
+        }
+
+        if let Some(pipeline) = planned.pipeline {
+            self.commands.push(RecordedCommand::SetPipeline(pipeline));
+        }
+        for bindings in planned.bindings.chunk_by(|a, b| a.group == b.group) {
+            self.commands.push(RecordedCommand::SetBindGroup {
+                group: bindings[0].group,
+                bindings: bindings.iter().map(|binding| (binding.binding, label(binding.resource))).collect(),
+            });
+        }
+        if let Some(viewport) = planned.viewport {
+            self.commands.push(RecordedCommand::SetViewport(viewport));
+        }
+
+        node.execute = None;
+        self.commands.push(RecordedCommand::EndPass);
+    }
+}
+
+#[derive(Default)]
+struct PhysicalResources {
+    textures: SecondaryMap<TextureHandle, PooledTexture>,
+    buffers: SecondaryMap<BufferHandle, PooledBuffer>,
+}
+
+impl PhysicalResources {
+    fn release(self, pool: &mut TransientPool) {
+        for (_, texture) in self.textures {
+            pool.release_texture(texture);
+        }
+        for (_, buffer) in self.buffers {
+            pool.release_buffer(buffer);
+        }
+    }
+
+    fn texture(&self, resource: ResourceHandle) -> &wgpu::Texture {
+        let ResourceHandle::Texture(handle) = resource else { panic!("Expected a texture") };
+        &self.textures.get(handle).expect("Texture was not allocated").texture
+    }
+
+    fn view(&self, resource: ResourceHandle) -> &wgpu::TextureView {
+        let ResourceHandle::Texture(handle) = resource else { panic!("Expected a texture") };
+        &self.textures.get(handle).expect("Texture was not allocated").view
+    }
+
+    fn buffer(&self, resource: ResourceHandle) -> &wgpu::Buffer {
+        let ResourceHandle::Buffer(handle) = resource else { panic!("Expected a buffer") };
+        &self.buffers.get(handle).expect("Buffer was not allocated").buffer
+    }
+
+    fn texture_copy_info(&self, resource: ResourceHandle, region: &TextureRegion) -> wgpu::TexelCopyTextureInfo<'_> {
+        wgpu::TexelCopyTextureInfo {
+            texture: self.texture(resource),
+            mip_level: region.mip_level,
+            origin: region.origin_3d(),
+            aspect: region.aspect,
+        }
+    }
+
+    fn binding_resource(&self, resource: ResourceHandle) -> wgpu::BindingResource<'_> {
+        match resource {
+            ResourceHandle::Texture(_) => wgpu::BindingResource::TextureView(self.view(resource)),
+            ResourceHandle::Buffer(_) => self.buffer(resource).as_entire_binding(),
+        }
+    }
+}
+
+fn clear_texture(
+    op: &ClearTextureOp,
+    plan: &ExecutionPlan,
+    device: &wgpu::Device,
+    physical: &PhysicalResources,
+    encoder: &mut wgpu::CommandEncoder,
+) {
+    let texture = physical.texture(op.target);
+
+    if device.features().contains(wgpu::Features::CLEAR_TEXTURE) {
+        encoder.clear_texture(texture, &op.range);
+        return;
+    }
+
+    let desc = plan.texture_desc(op.target);
+    let range = &op.range;
+    let mip_count = range.mip_level_count.unwrap_or(texture.mip_level_count() - range.base_mip_level);
+    let layer_count = range
+        .array_layer_count
+        .unwrap_or(texture.depth_or_array_layers() - range.base_array_layer);
+    let label = plan.label(op.target);
+
+    for mip_level in range.base_mip_level..range.base_mip_level + mip_count {
+        let mip_size = desc.size.mip_level_size(mip_level, desc.dimension);
+
+        if desc.usage.contains(wgpu::TextureUsages::RENDER_ATTACHMENT)
+            && desc.dimension == wgpu::TextureDimension::D2
+        {
+            for layer in range.base_array_layer..range.base_array_layer + layer_count {
+                let view = texture.create_view(&wgpu::TextureViewDescriptor {
+                    label: Some(&format!("{label} (clear view)")),
+                    dimension: Some(wgpu::TextureViewDimension::D2),
+                    aspect: range.aspect,
+                    base_mip_level: mip_level,
+                    mip_level_count: Some(1),
+                    base_array_layer: layer,
+                    array_layer_count: Some(1),
+                    ..Default::default()
+                });
+                clear_with_render_pass(encoder, &format!("Clear {label}"), desc.format, &view);
+            }
+        } else {
+            // zero-initialized staging buffer, copied over the whole mip
+            let (_, block_height) = desc.format.block_dimensions();
+            let aspect = match range.aspect {
+                wgpu::TextureAspect::All if desc.format.is_depth_stencil_format() => wgpu::TextureAspect::DepthOnly,
+                aspect => aspect,
+            };
+            let bytes_per_row = padded_bytes_per_row(desc.format, aspect, mip_size.width);
+            let rows = mip_size.height.div_ceil(block_height);
+            let layers = match desc.dimension {
+                wgpu::TextureDimension::D3 => mip_size.depth_or_array_layers,
+                _ => layer_count,
+            };
+
+            let zeros = device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some(&format!("{label}/Clear")),
+                size: bytes_per_row as u64 * rows as u64 * layers as u64,
+                usage: wgpu::BufferUsages::COPY_SRC,
+                mapped_at_creation: false,
+            });
+            let base_layer = match desc.dimension {
+                wgpu::TextureDimension::D3 => 0,
+                _ => range.base_array_layer,
+            };
+
+            encoder.copy_buffer_to_texture(
+                wgpu::TexelCopyBufferInfo {
+                    buffer: &zeros,
+                    layout: wgpu::TexelCopyBufferLayout {
+                        offset: 0,
+                        bytes_per_row: Some(bytes_per_row),
+                        rows_per_image: Some(rows),
+                    },
+                },
+                wgpu::TexelCopyTextureInfo {
+                    texture,
+                    mip_level,
+                    origin: wgpu::Origin3d { x: 0, y: 0, z: base_layer },
+                    aspect,
+                },
+                wgpu::Extent3d {
+                    width: mip_size.width,
+                    height: mip_size.height,
+                    depth_or_array_layers: layers,
+                },
+            );
+        }
+    }
+}
+
+fn clear_with_render_pass(
+    encoder: &mut wgpu::CommandEncoder,
+    label: &str,
+    format: wgpu::TextureFormat,
+    view: &wgpu::TextureView,
+) {
+    let color_attachment = (!format.is_depth_stencil_format()).then_some(wgpu::RenderPassColorAttachment {
+        view,
+        depth_slice: None,
+        resolve_target: None,
+        ops: wgpu::Operations {
+            load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+            store: wgpu::StoreOp::Store,
+        },
+    });
+    let depth_stencil_attachment = format.is_depth_stencil_format().then(|| wgpu::RenderPassDepthStencilAttachment {
+        view,
+        depth_ops: format.has_depth_aspect().then_some(wgpu::Operations {
+            load: wgpu::LoadOp::Clear(0.0),
+            store: wgpu::StoreOp::Store,
+        }),
+        stencil_ops: format.has_stencil_aspect().then_some(wgpu::Operations {
+            load: wgpu::LoadOp::Clear(0),
+            store: wgpu::StoreOp::Store,
+        }),
+    });
+
+    let color_attachments: Vec<_> = color_attachment.into_iter().map(Some).collect();
+
+    encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+        label: Some(label),
+        color_attachments: &color_attachments,
+        depth_stencil_attachment,
+        timestamp_writes: None,
+        occlusion_query_set: None,
+        multiview_mask: None,
+    });
+}
+
+fn create_staging_buffer(device: &wgpu::Device, label: &str, data: &[u8]) -> wgpu::Buffer {
+    // buffer copies must be multiples of COPY_BUFFER_ALIGNMENT
+    let size = (data.len() as u64).next_multiple_of(wgpu::COPY_BUFFER_ALIGNMENT);
+    let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some(&format!("{label}/Staging")),
+        size,
+        usage: wgpu::BufferUsages::COPY_SRC,
+        mapped_at_creation: true,
+    });
+    buffer.slice(..).get_mapped_range_mut()[..data.len()].copy_from_slice(data);
+    buffer.unmap();
+    buffer
+}
@@ -0,0 +1,80 @@
+use crate::gpu::render_graph::graph::RenderGraph;
+use crate::gpu::render_graph::resource_pool::Resources;
+
+/// Blackboard name of the texture shown in the window, upscaled from the render size. It needs
+/// `TEXTURE_BINDING` usage.
+pub const SCENE_COLOR: &str = "scene_color";
+
+/// Per-frame data handed to every render feature.
+#[derive(Clone, Copy)]
+pub struct FrameInputs {
+    // seconds since the previous frame
+    pub delta_time: f32,
+    // internal resolution picked by dynamic resolution; render here and publish SCENE_COLOR
+    pub render_width: u32,
+    pub render_height: u32,
+}
+
+/// An independently developed piece of the frame (shadows, SSAO, bloom, UI...).
+///
+/// Features create their persistent GPU objects in `setup` and add their passes every frame in
+/// `build`. Handles other features need are exchanged through `RenderGraph::blackboard`.
+pub trait RenderFeature {
+    fn name(&self) -> &str;
+
+    fn setup(&mut self, resources: &mut Resources);
+
+    fn build(&mut self, graph: &mut RenderGraph, inputs: &FrameInputs);
+
+    fn resize(&mut self, _resources: &mut Resources, _width: u32, _height: u32) {}
+
+    fn teardown(&mut self, _resources: &mut Resources) {}
+}
+
+/// Registered features, built into the graph in registration order.
+pub struct RenderFeatures {
+    features: Vec<Box<dyn RenderFeature>>,
+}
+
+impl RenderFeatures {
+    pub fn new() -> Self {
+        RenderFeatures {
+            features: Vec::new(),
+        }
+    }
+
+    pub fn register<F: RenderFeature + 'static>(&mut self, feature: F) {
+        self.features.push(Box::new(feature));
+    }
+
+    pub fn setup(&mut self, resources: &mut Resources) {
+        for feature in self.features.iter_mut() {
+            feature.setup(resources);
+        }
+    }
+
+    pub fn build(&mut self, graph: &mut RenderGraph, inputs: &FrameInputs) {
+        for feature in self.features.iter_mut() {
+            let name = feature.name().to_owned();
+            graph.scope(&name, |graph| feature.build(graph, inputs));
+        }
+    }
+
+    pub fn resize(&mut self, resources: &mut Resources, width: u32, height: u32) {
+        for feature in self.features.iter_mut() {
+            feature.resize(resources, width, height);
+        }
+    }
+
+    pub fn teardown(&mut self, resources: &mut Resources) {
+        for feature in self.features.iter_mut().rev() {
+            feature.teardown(resources);
+        }
+    }
+}
+
+impl Default for RenderFeatures {
+    fn default() -> Self {
+        Self::new()
+    }
+}
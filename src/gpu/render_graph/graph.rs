@@ -1,14 +1,27 @@
-use std::collections::{HashMap, VecDeque};
+use std::collections::{HashMap, HashSet};
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::num::NonZeroU32;
+use std::path::Path;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use bytemuck::{Pod, Zeroable};
-use slotmap::SparseSecondaryMap;
+use serde_json::json;
+use slotmap::SecondaryMap;
 use wgpu::{CommandEncoder, RenderPassDescriptor, wgt::CommandEncoderDescriptor};
 
+use crate::gpu::profiler::Profiler;
+use crate::gpu::render_graph::blackboard::Blackboard;
+use crate::gpu::render_graph::executor::{GpuState, GraphExecutor, RecordingExecutor, WgpuExecutor};
+use crate::gpu::render_graph::plan::{ExecutionPlan, PlanStep, PlannedNode, PlannedResource, ResourceDesc, ResourceLifetime};
 use crate::gpu::render_graph::registry::InstanceRegistry;
 use crate::gpu::render_graph::resource_pool::Resources;
+use crate::gpu::render_graph::schedule::{self, ScheduleReport, SchedulingStrategy};
+use crate::gpu::render_graph::subgraph::SubgraphOutputs;
+use crate::gpu::render_graph::transient_pool::PoolStats;
+use crate::gpu::render_graph::view::{View, ViewContext, ViewUniforms};
 use crate::gpu::render_graph::types::{
-    BufferDesc, BufferHandle, CopyOp, DownloadOp, Node, NodeInput, NodeOutput, NodeType, PassContext, PipelineHandle, ResourceHandle, ResourceType, TextureDesc, TextureHandle, UploadOp
+    BufferDesc, BufferHandle, ClearBufferOp, ClearTextureOp, CopyOp, GraphError, Node, NodeBinding, NodeInput, NodeOutput, NodeType, PassContext, PipelineHandle, ResourceHandle, ResourceType, TextureCopyOp, TextureDesc, TextureHandle, TextureRegion, TextureUploadOp, TransferOps, UploadOp, Viewport, unpadded_bytes_per_row
 };
 
 pub struct RenderGraph {
@@ -17,8 +30,29 @@ pub struct RenderGraph {
     textures: InstanceRegistry<TextureHandle, TextureDesc>,
     buffers: InstanceRegistry<BufferHandle, BufferDesc>,
 
-    texture_cache: HashMap<TextureDesc, CachedTexture>,
-    buffer_cache: HashMap<BufferDesc, CachedBuffer>,
+    // full debug labels ("GBuffer/Albedo"), prefixed by the scope they were created in
+    texture_labels: SecondaryMap<TextureHandle, String>,
+    buffer_labels: SecondaryMap<BufferHandle, String>,
+
+    scopes: Vec<DebugScope>,
+    current_scope: Option<usize>,
+    // viewport of the view being built by `add_views`, given to its render passes
+    current_viewport: Option<Viewport>,
+    // subgraph instance owning each transient created inside it
+    resource_owners: HashMap<ResourceHandle, usize>,
+
+    blackboard: Blackboard,
+    // roots of unused-node culling, see `mark_output`
+    outputs: Vec<ResourceHandle>,
+
+    scheduling: SchedulingStrategy,
+
+    // survive `begin_frame`; reused while `structure_hash` matches
+    plan: Option<ExecutionPlan>,
+    gpu_state: GpuState,
+    compile_stats: CompileStats,
+
+    culled: Vec<String>,
 }
 
 impl RenderGraph {
@@ -29,28 +63,266 @@ impl RenderGraph {
             textures: InstanceRegistry::new(),
             buffers: InstanceRegistry::new(),
 
-            texture_cache: HashMap::new(),
-            buffer_cache: HashMap::new(),
+            texture_labels: SecondaryMap::new(),
+            buffer_labels: SecondaryMap::new(),
+
+            scopes: Vec::new(),
+            current_scope: None,
+            current_viewport: None,
+            resource_owners: HashMap::new(),
+
+            blackboard: Blackboard::new(),
+            outputs: Vec::new(),
+
+            scheduling: SchedulingStrategy::default(),
+
+            plan: None,
+            gpu_state: GpuState::default(),
+            compile_stats: CompileStats::default(),
+
+            culled: Vec::new(),
         }
     }
 
-    pub fn add_texture(&mut self, desc: TextureDesc) -> ResourceHandle {
-        ResourceHandle::Texture(self.textures.insert(desc))
+    pub fn add_texture(&mut self, name: &str, desc: TextureDesc) -> ResourceHandle {
+        let handle = self.textures.insert(desc);
+        self.texture_labels.insert(handle, self.scoped_label(name));
+        self.register_owner(ResourceHandle::Texture(handle));
+        ResourceHandle::Texture(handle)
+    }
+
+    pub fn add_buffer(&mut self, name: &str, desc: BufferDesc) -> ResourceHandle {
+        let handle = self.buffers.insert(desc);
+        self.buffer_labels.insert(handle, self.scoped_label(name));
+        self.register_owner(ResourceHandle::Buffer(handle));
+        ResourceHandle::Buffer(handle)
+    }
+
+    /// Runs `build` inside a named debug scope. Resources created inside are labeled
+    /// "Scope/Name" and the scope's nodes are wrapped in a nested debug group.
+    pub fn scope<R>(&mut self, name: &str, build: impl FnOnce(&mut RenderGraph) -> R) -> R {
+        self.push_scope(name, false);
+        let result = build(self);
+        self.pop_scope();
+        result
     }
 
-    pub fn add_buffer(&mut self, desc: BufferDesc) -> ResourceHandle {
-        ResourceHandle::Buffer(self.buffers.insert(desc))
+    /// Instantiates a subgraph template under the instance name `name`, e.g.
+    /// `graph.subgraph("Bloom", |g| bloom(g, hdr))`. The same template can be instantiated any
+    /// number of times; each instance's transients are namespaced and private to it unless
+    /// `build` returns them.
+    pub fn subgraph<O: SubgraphOutputs>(
+        &mut self,
+        name: &str,
+        build: impl FnOnce(&mut RenderGraph) -> O,
+    ) -> O {
+        let instance = self.push_scope(name, true);
+        let outputs = build(self);
+        self.pop_scope();
+
+        // outputs become visible to whoever instantiated the subgraph
+        let parent_owner = self.owning_scope(self.current_scope);
+        outputs.for_each_resource(&mut |resource| {
+            if self.resource_owners.get(&resource) == Some(&instance) {
+                match parent_owner {
+                    Some(owner) => self.resource_owners.insert(resource, owner),
+                    None => self.resource_owners.remove(&resource),
+                };
+            }
+        });
+
+        outputs
+    }
+
+    /// Builds the passes of every view in `views`. Each view is its own subgraph instance named
+    /// after it, with a uniform buffer holding `View::uniforms` and its viewport applied to the
+    /// render passes it adds. View-independent passes (shadow maps, simulations) are added once,
+    /// outside, and read by the views.
+    pub fn add_views<O: SubgraphOutputs>(
+        &mut self,
+        views: &[View],
+        mut build: impl FnMut(&mut RenderGraph, &ViewContext) -> O,
+    ) -> Vec<O> {
+        let outer_viewport = self.current_viewport;
+
+        views
+            .iter()
+            .enumerate()
+            .map(|(index, view)| {
+                self.subgraph(&view.name, |graph| {
+                    let uniforms = graph.add_buffer(
+                        "ViewUniforms",
+                        BufferDesc {
+                            size: std::mem::size_of::<ViewUniforms>() as u64,
+                            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                            mapped_at_creation: false,
+                        },
+                    );
+                    graph
+                        .add_transfer("Upload view")
+                        .write(uniforms, 0, vec![view.uniforms])
+                        .finish();
+
+                    graph.current_viewport = view.viewport;
+                    let outputs = build(graph, &ViewContext { index, view, uniforms });
+                    graph.current_viewport = outer_viewport;
+                    outputs
+                })
+            })
+            .collect()
+    }
+
+    fn push_scope(&mut self, name: &str, isolated: bool) -> usize {
+        self.scopes.push(DebugScope {
+            name: name.into(),
+            parent: self.current_scope,
+            isolated,
+        });
+        let scope = self.scopes.len() - 1;
+        self.current_scope = Some(scope);
+        scope
+    }
+
+    fn pop_scope(&mut self) {
+        let scope = self.current_scope.expect("No scope to pop");
+        self.current_scope = self.scopes[scope].parent;
+    }
+
+    fn register_owner(&mut self, resource: ResourceHandle) {
+        if let Some(owner) = self.owning_scope(self.current_scope) {
+            self.resource_owners.insert(resource, owner);
+        }
+    }
+
+    // nearest enclosing subgraph instance
+    fn owning_scope(&self, scope: Option<usize>) -> Option<usize> {
+        let mut current = scope;
+        while let Some(idx) = current {
+            if self.scopes[idx].isolated {
+                return Some(idx);
+            }
+            current = self.scopes[idx].parent;
+        }
+        None
+    }
+
+    // Every resource private to a subgraph instance may only be touched by nodes inside it.
+    fn validate_scopes(&self) -> Result<(), GraphError> {
+        for node in self.nodes.iter() {
+            let chain = self.scope_chain(node.scope);
+
+            for resource in node.resources() {
+                if let Some(owner) = self.resource_owners.get(&resource)
+                    && !chain.contains(owner)
+                {
+                    return Err(GraphError::ScopeViolation {
+                        node: node.name.clone(),
+                        resource: self.resource_label(resource).into(),
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Every attachment of a multiview pass needs exactly one array layer per view.
+    fn validate_multiview(&self) -> Result<(), GraphError> {
+        for (idx, node) in self.nodes.iter().enumerate() {
+            let Some(mask) = node.multiview_mask else { continue; };
+            let views = u32::BITS - mask.leading_zeros();
+
+            let attachments = node.outputs.iter().map(|output| output.resource).chain(node.depth_texture);
+            for resource in attachments {
+                let Some(desc) = self.texture_desc(resource) else { continue; };
+                if desc.size.depth_or_array_layers != views {
+                    return Err(GraphError::MultiviewLayers {
+                        node: self.node_label(idx),
+                        resource: self.resource_label(resource).into(),
+                        layers: desc.size.depth_or_array_layers,
+                        views,
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn texture_desc(&self, resource: ResourceHandle) -> Option<&TextureDesc> {
+        match resource {
+            ResourceHandle::Texture(handle) => self.textures.get(handle),
+            ResourceHandle::Buffer(_) => None,
+        }
+    }
+
+    pub fn blackboard(&self) -> &Blackboard {
+        &self.blackboard
+    }
+
+    pub fn blackboard_mut(&mut self) -> &mut Blackboard {
+        &mut self.blackboard
+    }
+
+    /// Marks `resource` as a result of the frame, e.g. the image presented afterwards. Once
+    /// anything is marked, `plan` culls the nodes no marked output depends on; without outputs
+    /// every enabled node runs.
+    pub fn mark_output(&mut self, resource: ResourceHandle) {
+        if !self.outputs.contains(&resource) {
+            self.outputs.push(resource);
+        }
+    }
+
+    pub fn resource_label(&self, resource: ResourceHandle) -> &str {
+        let label = match resource {
+            ResourceHandle::Texture(handle) => self.texture_labels.get(handle),
+            ResourceHandle::Buffer(handle) => self.buffer_labels.get(handle),
+        };
+        label.map(String::as_str).unwrap_or("Unnamed resource")
+    }
+
+    fn scoped_label(&self, name: &str) -> String {
+        match self.current_scope {
+            Some(scope) => format!("{}/{}", self.scope_path(scope), name),
+            None => name.into(),
+        }
+    }
+
+    fn scope_path(&self, scope: usize) -> String {
+        let mut names = Vec::new();
+        let mut current = Some(scope);
+        while let Some(idx) = current {
+            names.push(self.scopes[idx].name.as_str());
+            current = self.scopes[idx].parent;
+        }
+        names.reverse();
+        names.join("/")
+    }
+
+    // root-first chain of scopes enclosing `scope`
+    fn scope_chain(&self, scope: Option<usize>) -> Vec<usize> {
+        let mut chain = Vec::new();
+        let mut current = scope;
+        while let Some(idx) = current {
+            chain.push(idx);
+            current = self.scopes[idx].parent;
+        }
+        chain.reverse();
+        chain
     }
 
     pub fn add_transfer(&mut self, name: &str) -> TransferBuilder {
+        let scope = self.current_scope;
         TransferBuilder {
             graph: self,
             name: name.into(),
             kind: NodeType::Transfer,
+            scope,
 
             upload_op: Vec::new(),
-            download_op: Vec::new(),
             copy_op: Vec::new(),
+            texture_upload_op: Vec::new(),
+            texture_copy_op: Vec::new(),
+            clear_buffer_op: Vec::new(),
+            clear_texture_op: Vec::new(),
         }
     }
 
@@ -58,205 +330,467 @@ impl RenderGraph {
         PassBuilder::new(self, name, kind)
     }
 
-    pub fn compile(&mut self, device: &wgpu::Device, resources: &Resources) {
-        let order = self.get_node_order();
-        if let Err(()) = order {
-            return;
-        }
+    /// Clears everything declared for the previous frame. The plan, physical resources and bind
+    /// groups are kept and reused while the structure is unchanged.
+    pub fn begin_frame(&mut self) {
+        self.nodes.clear();
 
-        let order = order.unwrap();
-
-        let mut texture_lt: SparseSecondaryMap<TextureHandle, ResourceLifetime> = SparseSecondaryMap::new();
-        let mut buffer_lt: SparseSecondaryMap<BufferHandle, ResourceLifetime> = SparseSecondaryMap::new();
-
-        for &i in order.iter() {
-            let node = &self.nodes[i];
-
-            let mut process_resource = |res: &ResourceHandle| {
-                match res {
-                    ResourceHandle::Buffer(handle) => {
-                        if let Some(lt) = buffer_lt.get_mut(*handle) {
-                            lt.last_use = i as u32;
-                        } else {
-                            buffer_lt.insert(*handle, ResourceLifetime { first_use: i as u32, last_use: i as u32 });
-                        }
-                    },
-                    ResourceHandle::Texture(handle) => {
-                        if let Some(lt) = texture_lt.get_mut(*handle) {
-                            lt.last_use = i as u32;
-                        } else {
-                            texture_lt.insert(*handle, ResourceLifetime { first_use: i as u32, last_use: i as u32 });
-                        }
-                    }
-                }
-            };
+        self.textures = InstanceRegistry::new();
+        self.buffers = InstanceRegistry::new();
+
+        self.texture_labels.clear();
+        self.buffer_labels.clear();
+
+        self.scopes.clear();
+        self.current_scope = None;
+        self.current_viewport = None;
+        self.resource_owners.clear();
 
-            for input in &node.inputs { process_resource(&input.resource); }
-            for output in &node.outputs { process_resource(&output.resource); }
+        self.blackboard = Blackboard::new();
+        self.outputs.clear();
+        self.culled.clear();
+    }
+
+    /// Plans the frame and records the plan with a `WgpuExecutor`.
+    pub fn compile(
+        &mut self,
+        device: &wgpu::Device,
+        resources: &mut Resources,
+        profiler: &mut Profiler,
+    ) -> Result<wgpu::CommandBuffer, GraphError> {
+        let start = Instant::now();
+
+        self.plan()?;
+        self.validate_pipelines(resources)?;
+
+        let mut gpu_state = std::mem::take(&mut self.gpu_state);
+        let mut executor = WgpuExecutor::new(device, resources, &mut gpu_state, profiler);
+        self.execute(&mut executor);
+        let commands = executor.finish();
+        self.gpu_state = gpu_state;
+        resources.transient_pool_mut().end_frame();
+
+        self.compile_stats.duration = start.elapsed();
+
+        Ok(commands)
+    }
+
+    /// Planning phase of `compile`, without touching the GPU: culls disabled nodes and nodes no
+    /// marked output depends on, validates the rest and hashes their structure. A frame hashing
+    /// like the previous one gets its plan back unchanged; otherwise the nodes are scheduled and
+    /// the resource lifetimes worked out anew. `benches/compile.rs` measures the difference.
+    pub fn plan(&mut self) -> Result<&ExecutionPlan, GraphError> {
+        self.cull_disabled_nodes();
+        self.cull_unused_nodes();
+        self.validate_scopes()?;
+        self.validate_multiview()?;
+
+        let hash = self.structure_hash();
+        let cache_hit = match &mut self.plan {
+            // culled nodes are not part of the hash
+            Some(plan) if plan.hash == hash => {
+                plan.culled.clone_from(&self.culled);
+                true
+            }
+            _ => {
+                self.plan = Some(self.build_plan(hash)?);
+                false
+            }
+        };
+        self.compile_stats = CompileStats { cache_hit, duration: Duration::ZERO };
+
+        Ok(self.plan.as_ref().expect("execution plan"))
+    }
+
+    // Multiview passes must use a pipeline created with the same mask, and other passes a
+    // pipeline without one.
+    fn validate_pipelines(&self, resources: &Resources) -> Result<(), GraphError> {
+        let Some(plan) = &self.plan else { return Ok(()); };
+
+        for planned in plan.nodes() {
+            let Some(PipelineHandle::Render(handle)) = planned.pipeline else { continue; };
+            let pipeline = resources.multiview_mask(handle);
+            if pipeline != planned.multiview_mask {
+                return Err(GraphError::MultiviewMismatch {
+                    node: planned.label.clone(),
+                    pass: planned.multiview_mask,
+                    pipeline,
+                });
+            }
         }
+        Ok(())
+    }
 
-        let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor {
-            label: Some("Command encoder"),
+    /// Execution phase of `compile`: runs the last plan on `executor`, consuming the pass
+    /// closures.
+    pub fn execute(&mut self, executor: &mut dyn GraphExecutor) {
+        let plan = self.plan.as_ref().expect("RenderGraph::plan must run before execute");
+
+        executor.prepare(plan);
+        for step in plan.steps.iter() {
+            match step {
+                PlanStep::PushDebugGroup(label) => executor.push_debug_group(label),
+                PlanStep::PopDebugGroup => executor.pop_debug_group(),
+                PlanStep::Node(planned) => executor.execute_node(plan, planned, &mut self.nodes[planned.node]),
+            }
+        }
+    }
+
+    /// Writes the last plan, the commands it records and the transient pool sizes as JSON, to
+    /// look at a frame's graph without a GPU debugger. Pass closures were consumed by `compile`
+    /// and are not run.
+    pub fn write_plan(&mut self, path: &Path, pool: PoolStats) -> std::io::Result<()> {
+        let mut recorder = RecordingExecutor::new();
+        self.execute(&mut recorder);
+
+        // the plan refers to resources by handle; list the nodes again with labels to read it
+        let resources = |resources: &[ResourceHandle]| -> Vec<_> {
+            resources
+                .iter()
+                .map(|&resource| {
+                    json!({
+                        "label": self.resource_label(resource),
+                        "allocation": self.physical_allocation(resource),
+                        "lifetime": self.resource_lifetime(resource),
+                    })
+                })
+                .collect()
+        };
+        let nodes: Vec<_> = self
+            .nodes()
+            .map(|node| {
+                json!({
+                    "label": node.label,
+                    "kind": node.kind,
+                    "reads": resources(&node.reads),
+                    "writes": resources(&node.writes),
+                })
+            })
+            .collect();
+
+        let dump = json!({
+            "plan": self.execution_plan(),
+            "order": self.execution_order(),
+            "nodes": nodes,
+            "culled": self.culled_nodes(),
+            "commands": recorder.commands(),
+            "transient_pool": pool,
         });
+        std::fs::write(path, serde_json::to_vec_pretty(&dump)?)
+    }
+
+    /// Whether the last `plan` reused the cached plan, and how long the last `compile` took on
+    /// the CPU.
+    pub fn compile_stats(&self) -> CompileStats {
+        self.compile_stats
+    }
+
+    // Everything that decides the compiled schedule and physical resources, but not the
+    // closures. Handles hash by slot, which is stable as long as a frame declares the same
+    // resources in the same order.
+    fn structure_hash(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+
+        self.scheduling.hash(&mut hasher);
+        self.nodes.len().hash(&mut hasher);
 
-        for &idx in order.iter() {
-            let node = &self.nodes[idx];
+        for (idx, node) in self.nodes.iter().enumerate() {
+            self.node_label(idx).hash(&mut hasher);
+            node.kind.hash(&mut hasher);
+            node.pipeline.hash(&mut hasher);
 
-            match node.kind {
-                NodeType::RenderPass => {
-                    self.compile_render_pass(idx, device, &mut encoder);
+            for input in node.inputs.iter() {
+                (0u8, input.binding, input.resource).hash(&mut hasher);
+            }
+            for output in node.outputs.iter() {
+                (1u8, output.binding, output.resource).hash(&mut hasher);
+            }
+            node.depth_texture.hash(&mut hasher);
+            node.bindings.hash(&mut hasher);
+            node.viewport.hash(&mut hasher);
+            node.multiview_mask.hash(&mut hasher);
+
+            for resource in node.resources() {
+                self.resource_label(resource).hash(&mut hasher);
+                match resource {
+                    ResourceHandle::Texture(handle) => self.textures.get(handle).hash(&mut hasher),
+                    ResourceHandle::Buffer(handle) => self.buffers.get(handle).hash(&mut hasher),
                 }
-                NodeType::ComputePass => {}
-                NodeType::Transfer => {}
             }
         }
-    }
 
-    fn compile_tranfer() {}
+        hasher.finish()
+    }
 
-    fn compile_render_pass(
-        &mut self,
-        node_idx: usize,
-        device: &wgpu::Device,
-        encoder: &mut wgpu::CommandEncoder,
-    ) {
-        let node = &mut self.nodes[node_idx];
-        let mut frame_views = Vec::new();
-        let mut color_attachments = Vec::new();
-
-        for output in node.outputs.iter() {
-            let ResourceHandle::Texture(handle) = output.resource else {continue;};
-
-            let desc = self.textures.get(handle).expect("Texture does not exist");
-            
-            let texture = device.create_texture(&wgpu::TextureDescriptor {
-                label: Some(&output.binding.to_string()),
-                size: desc.size,
-                mip_level_count: 0,
-                sample_count: 0,
-                dimension: desc.dimension,
-                format: desc.format,
-                usage: desc.usage,
-                view_formats: &[],
-            });
-
-            let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
-            frame_views.push(view);
-
-            self.texture_cache.insert(*desc, CachedTexture {
-                texture: texture,
-            });
-        }
-
-        for view in frame_views.iter() {
-            color_attachments.push(Some(wgpu::RenderPassColorAttachment {
-                view: view,
-                depth_slice: None,
-                resolve_target: None,
-                ops: wgpu::Operations {
-                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
-                    store: wgpu::StoreOp::Store,
-                },
-            }));
-        }
-
-        let depth_data = node.depth_texture.map(|handle| {
-            let ResourceHandle::Texture(h) = handle else { panic!("Depth must be a texture") };
-            let desc = self.textures.get(h).expect("Depth texture desc missing");
-            
-            let texture = device.create_texture(&wgpu::TextureDescriptor {
-                label: Some("Depth texture"),
-                size: desc.size,
-                mip_level_count: 0,
-                sample_count: 0,
-                dimension: desc.dimension,
-                format: desc.format,
-                usage: desc.usage,
-                view_formats: &[],
-            });
-            let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
-            
-            (texture, view)
-        });
-        // todo safe the depth texture for reuse
-
-        let depth_stencil_attachment = depth_data.as_ref().map(|(_tex, view)| {
-            wgpu::RenderPassDepthStencilAttachment {
-                view, // This is a &TextureView
-                depth_ops: Some(wgpu::Operations {
-                    load: wgpu::LoadOp::Clear(1.0),
-                    store: wgpu::StoreOp::Store,
-                }),
-                stencil_ops: None,
+    fn build_plan(&self, hash: u64) -> Result<ExecutionPlan, GraphError> {
+        let schedule = self.plan_schedule()?;
+
+        // lifetimes are measured in execution steps, not node indices
+        let mut lifetimes: HashMap<ResourceHandle, ResourceLifetime> = HashMap::new();
+        let mut first_used: Vec<ResourceHandle> = Vec::new();
+
+        for (step, &node_idx) in schedule.order.iter().enumerate() {
+            for resource in self.nodes[node_idx].resources() {
+                let step = step as u32;
+                lifetimes
+                    .entry(resource)
+                    .and_modify(|lt| lt.last_use = step)
+                    .or_insert_with(|| {
+                        first_used.push(resource);
+                        ResourceLifetime { first_use: step, last_use: step }
+                    });
             }
-        });
+        }
 
-        let render_pass_descriptor = wgpu::RenderPassDescriptor {
-            label: Some(&node.name),
-            color_attachments: &color_attachments,
-            depth_stencil_attachment: depth_stencil_attachment,
-            timestamp_writes: None,
-            occlusion_query_set: None,
-            multiview_mask: None,
+        // one physical allocation per resource, in order of first use
+        let resources = first_used
+            .iter()
+            .enumerate()
+            .map(|(allocation, &resource)| PlannedResource {
+                resource,
+                label: self.resource_label(resource).into(),
+                desc: self.resource_desc(resource),
+                lifetime: lifetimes[&resource],
+                allocation,
+            })
+            .collect();
+
+        let mut steps = Vec::new();
+        let mut open_scopes: Vec<usize> = Vec::new();
+        let mut touched: HashSet<ResourceHandle> = HashSet::new();
+
+        for &idx in schedule.order.iter() {
+            self.enter_scopes(self.nodes[idx].scope, &mut open_scopes, &mut steps);
+            steps.push(PlanStep::PushDebugGroup(self.nodes[idx].name.clone()));
+            steps.push(PlanStep::Node(self.plan_node(idx, &touched)));
+            steps.push(PlanStep::PopDebugGroup);
+            touched.extend(self.nodes[idx].resources());
+        }
+        steps.extend(open_scopes.iter().map(|_| PlanStep::PopDebugGroup));
+
+        Ok(ExecutionPlan::new(hash, schedule, resources, steps, self.culled.clone()))
+    }
+
+    // Pops debug groups of scopes the next node is not part of and pushes the ones it enters.
+    fn enter_scopes(&self, scope: Option<usize>, open_scopes: &mut Vec<usize>, steps: &mut Vec<PlanStep>) {
+        let chain = self.scope_chain(scope);
+        let shared = open_scopes
+            .iter()
+            .zip(chain.iter())
+            .take_while(|(open, next)| open == next)
+            .count();
+
+        while open_scopes.len() > shared {
+            open_scopes.pop();
+            steps.push(PlanStep::PopDebugGroup);
+        }
+        for &idx in &chain[shared..] {
+            steps.push(PlanStep::PushDebugGroup(self.scopes[idx].name.clone()));
+            open_scopes.push(idx);
+        }
+    }
+
+    // `touched` holds the resources of the nodes planned before this one.
+    fn plan_node(&self, node_idx: usize, touched: &HashSet<ResourceHandle>) -> PlannedNode {
+        let node = &self.nodes[node_idx];
+
+        let color_attachments = match node.kind {
+            NodeType::RenderPass => node
+                .outputs
+                .iter()
+                .map(|output| output.resource)
+                .filter(|resource| matches!(resource, ResourceHandle::Texture(_)))
+                .collect(),
+            _ => Vec::new(),
         };
 
+        let cleared = match node.kind {
+            NodeType::RenderPass => color_attachments
+                .iter()
+                .copied()
+                .chain(node.depth_texture)
+                .filter(|resource| !touched.contains(resource))
+                .collect(),
+            _ => Vec::new(),
+        };
 
-        let mut render_pass = encoder.begin_render_pass(&render_pass_descriptor);
+        let mut bindings = node.bindings.clone();
+        bindings.sort_by_key(|binding| (binding.group, binding.binding));
+
+        PlannedNode {
+            node: node_idx,
+            label: self.node_label(node_idx),
+            kind: node.kind,
+            pipeline: node.pipeline,
+            reads: node.inputs.iter().map(|input| input.resource).collect(),
+            writes: node.writes().collect(),
+            color_attachments,
+            depth_attachment: node.depth_texture,
+            cleared,
+            viewport: node.viewport,
+            multiview_mask: node.multiview_mask,
+            bindings,
+        }
+    }
 
-        
-        if let Some(ctx) = node.execute.take() {
-            ctx(PassContext::Render(&mut render_pass));
+    fn resource_desc(&self, resource: ResourceHandle) -> ResourceDesc {
+        match resource {
+            ResourceHandle::Texture(handle) => ResourceDesc::Texture(*self.textures.get(handle).expect("Texture does not exist")),
+            ResourceHandle::Buffer(handle) => ResourceDesc::Buffer(*self.buffers.get(handle).expect("Buffer does not exist")),
         }
     }
 
-    fn get_node_order(&self) -> Result<Vec<usize>, ()> {
-        let mut writers = HashMap::new();
+    fn node_label(&self, node_idx: usize) -> String {
+        let node = &self.nodes[node_idx];
+        self.scoped_name(node.scope, &node.name)
+    }
 
-        for (idx, node) in self.nodes.iter().enumerate() {
-            for res in node.outputs.iter() {
-                writers.insert(res.resource, idx);
-            }
+    fn scoped_name(&self, scope: Option<usize>, name: &str) -> String {
+        match scope {
+            Some(scope) => format!("{}/{}", self.scope_path(scope), name),
+            None => name.into(),
         }
+    }
 
-        let node_count = self.nodes.len();
-
-        let mut edges: Vec<Vec<usize>> = vec![Vec::new(); node_count];
-        let mut dependency_count = vec![0; node_count];
+    // Removes nodes whose condition is false. Downstream readers of their outputs read the
+    // declared fallback instead, or see the resource as never written.
+    fn cull_disabled_nodes(&mut self) {
+        let mut aliases: HashMap<ResourceHandle, ResourceHandle> = HashMap::new();
+        let mut kept = Vec::new();
+
+        for node in std::mem::take(&mut self.nodes) {
+            let enabled = node.condition.as_ref().is_none_or(|condition| condition());
+            if enabled {
+                kept.push(node);
+            } else {
+                aliases.extend(node.fallbacks.iter().copied());
+                self.culled.push(self.scoped_name(node.scope, &node.name));
+            }
+        }
 
-        for (reader_idx, node) in self.nodes.iter().enumerate() {
-            for input in node.inputs.iter() {
-                if let Some(&writer_idx) = writers.get(&input.resource) {
-                    edges[writer_idx].push(reader_idx);
-                    dependency_count[reader_idx] += 1;
+        // a fallback may itself be the output of another disabled node
+        let resolve = |mut resource: ResourceHandle| {
+            let mut hops = 0;
+            while let Some(&fallback) = aliases.get(&resource) {
+                resource = fallback;
+                hops += 1;
+                if hops > aliases.len() {
+                    break;
                 }
             }
+            resource
+        };
+        for node in kept.iter_mut() {
+            for input in node.inputs.iter_mut() {
+                input.resource = resolve(input.resource);
+            }
+            for binding in node.bindings.iter_mut() {
+                binding.resource = resolve(binding.resource);
+            }
         }
 
-        let mut queue = VecDeque::new();
-        let mut order = Vec::new();
+        self.nodes = kept;
+    }
 
-        for i in 0..node_count {
-            if dependency_count[i] == 0 {
-                queue.push_back(i);
+    // Removes nodes none of whose writes are needed. The marked outputs are needed; everything
+    // a kept node touches is needed too, including resources it writes again on top of earlier
+    // writes. Nodes that write nothing are kept.
+    fn cull_unused_nodes(&mut self) {
+        if self.outputs.is_empty() {
+            return;
+        }
+        let mut needed: HashSet<ResourceHandle> = self.outputs.iter().copied().collect();
+        let mut kept = vec![false; self.nodes.len()];
+
+        let mut changed = true;
+        while changed {
+            changed = false;
+            // consumers come after producers, so going backwards usually settles in one pass
+            for (idx, node) in self.nodes.iter().enumerate().rev() {
+                if kept[idx] {
+                    continue;
+                }
+                let mut writes = node.writes().peekable();
+                if writes.peek().is_none() || writes.any(|resource| needed.contains(&resource)) {
+                    kept[idx] = true;
+                    needed.extend(node.resources());
+                    changed = true;
+                }
             }
         }
 
-        while let Some(u) = queue.pop_front() {
-            order.push(u);
-
-            for &v in &edges[u] {
-                dependency_count[v] -= 1;
-                if dependency_count[v] == 0 {
-                    queue.push_back(v);
-                }
+        let mut kept = kept.into_iter();
+        let nodes = std::mem::take(&mut self.nodes);
+        for node in nodes {
+            if kept.next() == Some(true) {
+                self.nodes.push(node);
+            } else {
+                self.culled.push(self.scoped_name(node.scope, &node.name));
             }
         }
+    }
+
+    /// View of the texture behind `resource` after the last `compile`, for work recorded
+    /// outside the graph (e.g. presenting it).
+    pub fn texture_view(&self, resource: ResourceHandle) -> Option<&wgpu::TextureView> {
+        self.gpu_state.texture_view(resource)
+    }
+
+    /// Orders the nodes with the current scheduling strategy and estimates the transient
+    /// memory that order needs, without touching the GPU.
+    pub fn plan_schedule(&self) -> Result<ScheduleReport, GraphError> {
+        schedule::schedule(&self.nodes, self.scheduling, &|resource| self.resource_size(resource))
+    }
 
-        if order.len() != node_count {
-            return Err(());
+    pub fn set_scheduling(&mut self, strategy: SchedulingStrategy) {
+        self.scheduling = strategy;
+    }
+
+    fn resource_size(&self, resource: ResourceHandle) -> u64 {
+        match resource {
+            ResourceHandle::Texture(handle) => self.textures.get(handle).map_or(0, |desc| desc.size_in_bytes()),
+            ResourceHandle::Buffer(handle) => self.buffers.get(handle).map_or(0, |desc| desc.size_in_bytes()),
         }
+    }
+}
 
-        return Ok(order);
+impl Default for RenderGraph {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Read-only views of the last plan, for tests and tools
+impl RenderGraph {
+    /// Plan built by the last `plan` or `compile`.
+    pub fn execution_plan(&self) -> Option<&ExecutionPlan> {
+        self.plan.as_ref()
+    }
+
+    /// Nodes of the last plan in execution order, with their label, kind and the resources
+    /// they declared as reads and writes.
+    pub fn nodes(&self) -> impl Iterator<Item = &PlannedNode> {
+        self.plan.iter().flat_map(ExecutionPlan::nodes)
+    }
+
+    /// Node indices in the order the last `plan` put them.
+    pub fn execution_order(&self) -> Option<&[usize]> {
+        self.plan.as_ref().map(|plan| plan.schedule.order.as_slice())
+    }
+
+    pub fn resource_lifetime(&self, resource: ResourceHandle) -> Option<ResourceLifetime> {
+        self.plan.as_ref()?.resource(resource).map(|planned| planned.lifetime)
+    }
+
+    /// Physical allocation backing `resource` in the last plan. Every resource has one of its
+    /// own; transients are not aliased.
+    pub fn physical_allocation(&self, resource: ResourceHandle) -> Option<usize> {
+        self.plan.as_ref()?.resource(resource).map(|planned| planned.allocation)
+    }
+
+    /// Labels of the nodes culled by the last `plan`, disabled or unused.
+    pub fn culled_nodes(&self) -> &[String] {
+        &self.culled
     }
 }
 
@@ -264,34 +798,173 @@ pub struct TransferBuilder<'a> {
     graph: &'a mut RenderGraph,
     name: String,
     kind: NodeType,
+    scope: Option<usize>,
 
     upload_op: Vec<UploadOp>,
-    download_op: Vec<DownloadOp>,
     copy_op: Vec<CopyOp>,
-    // readback_tickets: Vec<ReadbackTicket<T>>,
+    texture_upload_op: Vec<TextureUploadOp>,
+    texture_copy_op: Vec<TextureCopyOp>,
+    clear_buffer_op: Vec<ClearBufferOp>,
+    clear_texture_op: Vec<ClearTextureOp>,
 }
 
 impl<'a> TransferBuilder<'a> {
-    pub fn read<T: Pod + Zeroable>(
+    /// Writes `data` at `offset`, which must be 4-byte aligned. The data is zero-padded to a
+    /// multiple of 4 bytes, so the buffer needs room for the padding.
+    pub fn write<T: Pod + Zeroable>(
         mut self,
-        source: ResourceHandle,
+        dest: ResourceHandle,
         offset: u64,
-        size: u64,
+        data: Vec<T>,
     ) -> Self {
-        !todo!();
+        assert!(
+            offset.is_multiple_of(wgpu::COPY_BUFFER_ALIGNMENT),
+            "Buffer writes must start at a multiple of {} bytes, {} got offset {offset}",
+            wgpu::COPY_BUFFER_ALIGNMENT,
+            self.graph.resource_label(dest)
+        );
+        let mut data: Vec<u8> = bytemuck::cast_slice(&data).to_vec();
+        data.resize(data.len().next_multiple_of(wgpu::COPY_BUFFER_ALIGNMENT as usize), 0);
+
+        self.upload_op.push(UploadOp {
+            target: dest,
+            offset,
+            data,
+        });
         self
     }
 
-    pub fn write<T: Pod + Zeroable>(
+    /// Zeroes `size` bytes (or everything past `offset`) of a buffer, e.g. counters of append
+    /// buffers or histogram bins before the passes incrementing them.
+    pub fn clear_buffer(mut self, dest: ResourceHandle, offset: u64, size: Option<u64>) -> Self {
+        self.clear_buffer_op.push(ClearBufferOp {
+            target: dest,
+            offset,
+            size,
+        });
+        self
+    }
+
+    /// Writes `value` `count` times starting at `offset`, which must be 4-byte aligned. Like
+    /// `write`, the filled range is zero-padded to a multiple of 4 bytes; zero values become a
+    /// clear instead of an upload.
+    pub fn fill_buffer<T: Pod + Zeroable>(self, dest: ResourceHandle, offset: u64, value: T, count: usize) -> Self {
+        if bytemuck::bytes_of(&value).iter().all(|&byte| byte == 0) {
+            assert!(
+                offset.is_multiple_of(wgpu::COPY_BUFFER_ALIGNMENT),
+                "Buffer fills must start at a multiple of {} bytes, {} got offset {offset}",
+                wgpu::COPY_BUFFER_ALIGNMENT,
+                self.graph.resource_label(dest)
+            );
+            let size = (std::mem::size_of::<T>() * count) as u64;
+            self.clear_buffer(dest, offset, Some(size.next_multiple_of(wgpu::COPY_BUFFER_ALIGNMENT)))
+        } else {
+            self.write(dest, offset, vec![value; count])
+        }
+    }
+
+    /// Zeroes `range` of a texture; `ImageSubresourceRange::default()` clears all of it.
+    pub fn clear_texture(mut self, dest: ResourceHandle, range: wgpu::ImageSubresourceRange) -> Self {
+        self.clear_texture_op.push(ClearTextureOp {
+            target: dest,
+            range,
+        });
+        self
+    }
+
+    /// Uploads `data`, laid out as described by `layout`, into `region` of a texture. Panics if
+    /// the layout's rows are shorter than the region's or `data` ends before its last row.
+    pub fn write_texture<T: Pod + Zeroable>(
         mut self,
         dest: ResourceHandle,
-        offset: u64,
-        data: Vec<T>,
+        region: TextureRegion,
+        layout: wgpu::TexelCopyBufferLayout,
+        data: &[T],
     ) -> Self {
-        self.upload_op.push(UploadOp {
+        let label = self.graph.resource_label(dest);
+        let ResourceHandle::Texture(handle) = dest else {
+            panic!("write_texture needs a texture, {label} is a buffer; use write for buffers");
+        };
+        let desc = self.graph.textures.get(handle).expect("Texture does not exist");
+        let (_, block_height) = desc.format.block_dimensions();
+
+        let row_bytes = unpadded_bytes_per_row(desc.format, region.aspect, region.extent.width) as u64;
+        let rows = region.extent.height.div_ceil(block_height) as u64;
+        let layers = region.extent.depth_or_array_layers as u64;
+        let row_pitch = layout.bytes_per_row.map_or(row_bytes, u64::from);
+        let rows_per_image = layout.rows_per_image.map_or(rows, u64::from);
+        assert!(
+            row_pitch >= row_bytes,
+            "Upload into {label}: bytes_per_row is {row_pitch}, but a row of the region has {row_bytes} bytes"
+        );
+        assert!(
+            rows_per_image >= rows,
+            "Upload into {label}: rows_per_image is {rows_per_image}, but the region has {rows} rows"
+        );
+
+        let data: Vec<u8> = bytemuck::cast_slice(data).to_vec();
+        if rows > 0 && layers > 0 {
+            // the last row only needs its own bytes, not a whole pitch
+            let needed = layout.offset + (layers - 1) * rows_per_image * row_pitch + (rows - 1) * row_pitch + row_bytes;
+            assert!(
+                data.len() as u64 >= needed,
+                "Upload into {label}: the region needs {needed} bytes of data with this layout, got {}",
+                data.len()
+            );
+        }
+
+        self.texture_upload_op.push(TextureUploadOp {
             target: dest,
-            offset: offset,
-            data: bytemuck::cast_vec(data),
+            region,
+            layout,
+            data,
+        });
+        self
+    }
+
+    /// Copies `region` between a texture and a buffer (either direction) or between two
+    /// textures. Buffer rows are `padded_bytes_per_row` apart, starting at `buffer_offset`.
+    pub fn copy_texture(
+        mut self,
+        src: ResourceHandle,
+        dst: ResourceHandle,
+        region: TextureRegion,
+        buffer_offset: u64,
+    ) -> Self {
+        assert!(
+            matches!(src, ResourceHandle::Texture(_)) || matches!(dst, ResourceHandle::Texture(_)),
+            "copy_texture needs a texture on at least one side, use copy for buffers"
+        );
+        self.texture_copy_op.push(TextureCopyOp {
+            src,
+            dst,
+            region,
+            dst_region: region,
+            buffer_offset,
+        });
+        self
+    }
+
+    /// Copies `src_region` of a texture into another texture at `dst_region`, e.g. one array
+    /// layer into one half of a side-by-side image. Both regions must have the same extent.
+    pub fn copy_texture_to(
+        mut self,
+        src: ResourceHandle,
+        src_region: TextureRegion,
+        dst: ResourceHandle,
+        dst_region: TextureRegion,
+    ) -> Self {
+        assert!(
+            matches!(src, ResourceHandle::Texture(_)) && matches!(dst, ResourceHandle::Texture(_)),
+            "copy_texture_to copies between textures"
+        );
+        assert!(src_region.extent == dst_region.extent, "Source and destination extents differ");
+        self.texture_copy_op.push(TextureCopyOp {
+            src,
+            dst,
+            region: src_region,
+            dst_region,
+            buffer_offset: 0,
         });
         self
     }
@@ -315,14 +988,53 @@ impl<'a> TransferBuilder<'a> {
     }
 
     pub fn finish(mut self) {
+        let sources = self
+            .copy_op
+            .iter()
+            .map(|op| op.src)
+            .chain(self.texture_copy_op.iter().map(|op| op.src));
+        let targets = self
+            .clear_buffer_op
+            .iter()
+            .map(|op| op.target)
+            .chain(self.clear_texture_op.iter().map(|op| op.target))
+            .chain(self.upload_op.iter().map(|op| op.target))
+            .chain(self.copy_op.iter().map(|op| op.dst))
+            .chain(self.texture_upload_op.iter().map(|op| op.target))
+            .chain(self.texture_copy_op.iter().map(|op| op.dst));
+
+        // bindings mean nothing for transfers, they only order the node against others
+        let inputs = sources
+            .enumerate()
+            .map(|(binding, resource)| NodeInput { binding: binding as u32, resource })
+            .collect();
+        let outputs = targets
+            .enumerate()
+            .map(|(binding, resource)| NodeOutput { binding: binding as u32, resource })
+            .collect();
+
         let transfer = Node {
             name: self.name,
             kind: self.kind,
-            inputs: Vec::new(),
-            outputs: Vec::new(),
+            scope: self.scope,
+            inputs,
+            outputs,
             depth_texture: None,
+            bindings: Vec::new(),
             pipeline: None,
+            viewport: None,
+            multiview_mask: None,
             execute: None,
+            condition: None,
+            fallbacks: Vec::new(),
+            transfer: Some(TransferOps {
+                clear_buffer: self.clear_buffer_op,
+                clear_texture: self.clear_texture_op,
+                upload: self.upload_op,
+                copy: self.copy_op,
+                texture_upload: self.texture_upload_op,
+                texture_copy: self.texture_copy_op,
+            }),
         };
 
         self.graph.nodes.push(transfer);
@@ -333,26 +1045,44 @@ pub struct PassBuilder<'a> {
     graph: &'a mut RenderGraph,
     name: String,
     kind: NodeType,
+    scope: Option<usize>,
 
     inputs: Vec<NodeInput>,
     outputs: Vec<NodeOutput>,
     depth_texture: Option<ResourceHandle>,
+    bindings: Vec<NodeBinding>,
     next_bind_idx: u32,
 
     pipeline: Option<PipelineHandle>,
+    viewport: Option<Viewport>,
+    multiview_mask: Option<NonZeroU32>,
+
+    condition: Option<Box<dyn Fn() -> bool>>,
+    fallbacks: Vec<(ResourceHandle, ResourceHandle)>,
 }
 
 impl<'a> PassBuilder<'a> {
     pub fn new(graph: &'a mut RenderGraph, name: &str, kind: NodeType) -> Self {
+        let scope = graph.current_scope;
+        let viewport = match kind {
+            NodeType::RenderPass => graph.current_viewport,
+            _ => None,
+        };
         PassBuilder {
-            graph: graph,
+            graph,
             name: name.into(),
-            kind: kind,
+            kind,
+            scope,
             inputs: Vec::new(),
             outputs: Vec::new(),
             depth_texture: None,
+            bindings: Vec::new(),
             next_bind_idx: 0,
             pipeline: None,
+            viewport,
+            multiview_mask: None,
+            condition: None,
+            fallbacks: Vec::new(),
         }
     }
 
@@ -380,27 +1110,86 @@ impl<'a> PassBuilder<'a> {
         self
     }
 
+    /// Sets `pipeline` and the bind groups of `bind` before the closure runs.
     pub fn use_pipeline(mut self, pipeline: PipelineHandle) -> Self {
         self.pipeline = Some(pipeline);
         self
     }
 
+    /// Binds `resource` at `@group(group) @binding(binding)` of the pass's pipeline, with the
+    /// layout the pipeline has for that group. Resources not declared with `write` or
+    /// `read_write` are read.
+    pub fn bind(mut self, group: u32, binding: u32, resource: ResourceHandle) -> Self {
+        self.bindings.push(NodeBinding { group, binding, resource });
+        self
+    }
+
+    /// Draws into `viewport` of the attachments, keeping what is outside of it. Passes added by
+    /// `add_views` get their view's viewport already.
+    pub fn viewport(mut self, viewport: Viewport) -> Self {
+        self.viewport = Some(viewport);
+        self
+    }
+
+    /// Renders `views` views at once, one per array layer of every attachment (e.g. 2 for
+    /// stereo). The pipeline must be created with the matching `multiview_mask`.
+    pub fn multiview(mut self, views: u32) -> Self {
+        assert!((1..=u32::BITS).contains(&views), "Multiview needs between 1 and 32 views");
+        self.multiview_mask = NonZeroU32::new(u32::MAX >> (u32::BITS - views));
+        self
+    }
+
+    /// Runs the pass only if `condition` returns true at compile time. Handles written by a
+    /// disabled pass stay valid; downstream passes just don't see its writes.
+    pub fn enabled_if<F>(mut self, condition: F) -> Self
+    where
+        F: Fn() -> bool + 'static,
+    {
+        self.condition = Some(Box::new(condition));
+        self
+    }
+
+    /// While the pass is disabled, passes reading `output` read `fallback` instead.
+    pub fn fallback(mut self, output: ResourceHandle, fallback: ResourceHandle) -> Self {
+        self.fallbacks.push((output, fallback));
+        self
+    }
+
     pub fn execute<F>(mut self, func: F)
     where
         F: FnOnce(PassContext) + 'static,
     {
+        for binding in self.bindings.clone() {
+            if !self.declares(binding.resource) {
+                self = self.read(binding.resource);
+            }
+        }
+
         let pass = Node {
             name: self.name,
             kind: self.kind,
+            scope: self.scope,
             inputs: self.inputs,
             outputs: self.outputs,
             depth_texture: self.depth_texture,
+            bindings: self.bindings,
             pipeline: self.pipeline,
+            viewport: self.viewport,
+            multiview_mask: self.multiview_mask,
             execute: Some(Box::new(func)),
+            condition: self.condition,
+            fallbacks: self.fallbacks,
+            transfer: None,
         };
         self.graph.nodes.push(pass);
     }
 
+    fn declares(&self, resource: ResourceHandle) -> bool {
+        self.inputs.iter().any(|input| input.resource == resource)
+            || self.outputs.iter().any(|output| output.resource == resource)
+            || self.depth_texture == Some(resource)
+    }
+
     fn get_next_bind_idx(&mut self) -> u32 {
         let binding = self.next_bind_idx;
         self.next_bind_idx += 1;
@@ -413,15 +1202,536 @@ struct NodeHandle {
     idx: usize,
 }
 
-struct ResourceLifetime {
-    first_use: u32,
-    last_use: u32,
+struct DebugScope {
+    name: String,
+    parent: Option<usize>,
+    // subgraph instances own the transients created inside them
+    isolated: bool,
 }
 
-struct CachedTexture {
-    texture: wgpu::Texture,
-}
-struct CachedBuffer {
-    buffer: wgpu::Buffer,
+#[derive(Clone, Copy, Default, Debug)]
+pub struct CompileStats {
+    pub cache_hit: bool,
+    pub duration: Duration,
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gpu::context::test_device;
+    use crate::gpu::render_graph::executor::RecordedCommand;
+    use crate::gpu::render_graph::resource_pool::{FragmentState, RenderPipelineDesc, VertexState};
+    use crate::gpu::render_graph::view::side_by_side;
+
+    fn add_target(graph: &mut RenderGraph, name: &str) -> ResourceHandle {
+        graph.add_texture(
+            name,
+            TextureDesc {
+                size: wgpu::Extent3d { width: 64, height: 64, depth_or_array_layers: 1 },
+                dimension: wgpu::TextureDimension::D2,
+                format: wgpu::TextureFormat::Rgba8Unorm,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            },
+        )
+    }
+
+    fn record(graph: &mut RenderGraph) -> Vec<RecordedCommand> {
+        graph.plan().expect("graph should plan");
+        let mut recorder = RecordingExecutor::new();
+        graph.execute(&mut recorder);
+        recorder.commands().to_vec()
+    }
+
+    fn node_labels(graph: &RenderGraph) -> Vec<String> {
+        graph.nodes().map(|planned| planned.label.clone()).collect()
+    }
+
+    #[test]
+    fn records_passes_in_dependency_order() {
+        let mut graph = RenderGraph::new();
+        let hdr = add_target(&mut graph, "HDR");
+        let shadow = add_target(&mut graph, "Shadow");
+        let albedo = add_target(&mut graph, "Albedo");
+        graph.mark_output(hdr);
+
+        // declared before its inputs' producers, still scheduled after them
+        graph
+            .add_pass("Lighting", NodeType::RenderPass)
+            .bind(0, 1, albedo)
+            .bind(0, 0, shadow)
+            .write(hdr)
+            .execute(|_| {});
+        graph.add_pass("Shadow", NodeType::RenderPass).write(shadow).execute(|_| {});
+        graph.add_pass("GBuffer", NodeType::RenderPass).write(albedo).execute(|_| {});
+
+        let commands = record(&mut graph);
+        assert_eq!(node_labels(&graph), ["Shadow", "GBuffer", "Lighting"]);
+        let lighting = graph.nodes().last().unwrap();
+        assert_eq!(lighting.kind, NodeType::RenderPass);
+        assert_eq!(lighting.reads, [albedo, shadow]);
+        assert_eq!(lighting.writes, [hdr]);
+
+        let pass = |label: &str, target: &str| {
+            vec![
+                RecordedCommand::PushDebugGroup(label.into()),
+                RecordedCommand::BeginRenderPass {
+                    label: label.into(),
+                    color_attachments: vec![target.into()],
+                    depth_attachment: None,
+                    cleared: vec![target.into()],
+                    multiview_mask: None,
+                },
+            ]
+        };
+        let mut expected = vec![
+            RecordedCommand::Allocate { resource: "Shadow".into(), allocation: 0 },
+            RecordedCommand::Allocate { resource: "Albedo".into(), allocation: 1 },
+            RecordedCommand::Allocate { resource: "HDR".into(), allocation: 2 },
+        ];
+        expected.extend(pass("Shadow", "Shadow"));
+        expected.extend([RecordedCommand::EndPass, RecordedCommand::PopDebugGroup]);
+        expected.extend(pass("GBuffer", "Albedo"));
+        expected.extend([RecordedCommand::EndPass, RecordedCommand::PopDebugGroup]);
+        expected.extend(pass("Lighting", "HDR"));
+        expected.extend([
+            RecordedCommand::SetBindGroup { group: 0, bindings: vec![(0, "Shadow".into()), (1, "Albedo".into())] },
+            RecordedCommand::EndPass,
+            RecordedCommand::PopDebugGroup,
+        ]);
+        assert_eq!(commands, expected);
+    }
+
+    // Two chains each shrinking a large texture into a small one, combined at the end. Run in
+    // submission order both large textures are alive at once; finishing one chain before
+    // starting the other keeps only one of them alive.
+    fn add_two_chains(graph: &mut RenderGraph) {
+        let sized = |graph: &mut RenderGraph, name: &str, width: u32| {
+            graph.add_texture(
+                name,
+                TextureDesc {
+                    size: wgpu::Extent3d { width, height: width, depth_or_array_layers: 1 },
+                    dimension: wgpu::TextureDimension::D2,
+                    format: wgpu::TextureFormat::Rgba8Unorm,
+                    usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+                },
+            )
+        };
+        let near_full = sized(graph, "Near full", 256);
+        let far_full = sized(graph, "Far full", 256);
+        let near_half = sized(graph, "Near half", 64);
+        let far_half = sized(graph, "Far half", 64);
+        let output = sized(graph, "Output", 64);
+        graph.mark_output(output);
+
+        graph.add_pass("Near", NodeType::RenderPass).write(near_full).execute(|_| {});
+        graph.add_pass("Far", NodeType::RenderPass).write(far_full).execute(|_| {});
+        graph.add_pass("Near downsample", NodeType::RenderPass).read(near_full).write(near_half).execute(|_| {});
+        graph.add_pass("Far downsample", NodeType::RenderPass).read(far_full).write(far_half).execute(|_| {});
+        graph.add_pass("Combine", NodeType::RenderPass).read(near_half).read(far_half).write(output).execute(|_| {});
+    }
+
+    #[test]
+    fn minimize_memory_lowers_the_peak() {
+        const FULL: u64 = 256 * 256 * 4;
+        const HALF: u64 = 64 * 64 * 4;
+
+        let mut graph = RenderGraph::new();
+        add_two_chains(&mut graph);
+
+        let submission = graph.plan_schedule().unwrap();
+        assert_eq!(submission.order, [0, 1, 2, 3, 4]);
+        // both full textures plus the first half one, before "Near downsample" releases its input
+        assert_eq!(submission.peak_bytes, 2 * FULL + HALF);
+        assert_eq!(submission.total_bytes, 2 * FULL + 3 * HALF);
+
+        for cluster_attachments in [false, true] {
+            graph.set_scheduling(SchedulingStrategy::MinimizeMemory { cluster_attachments });
+            let report = graph.plan_schedule().unwrap();
+            assert_eq!(report.order, [0, 2, 1, 3, 4]);
+            // "Far downsample" with "Far full" and both half textures
+            assert_eq!(report.peak_bytes, FULL + 2 * HALF);
+            assert_eq!(report.total_bytes, submission.total_bytes);
+        }
+
+        // the plan follows the strategy
+        record(&mut graph);
+        assert_eq!(node_labels(&graph), ["Near", "Near downsample", "Far", "Far downsample", "Combine"]);
+        assert_eq!(graph.execution_plan().unwrap().schedule.peak_bytes, FULL + 2 * HALF);
+    }
+
+    #[test]
+    fn culls_disabled_and_unused_nodes() {
+        let mut graph = RenderGraph::new();
+        let hdr = add_target(&mut graph, "HDR");
+        let ao = add_target(&mut graph, "AO");
+        let white = add_target(&mut graph, "White");
+        let overlay = add_target(&mut graph, "Overlay");
+        graph.mark_output(hdr);
+
+        graph
+            .add_transfer("Clear AO")
+            .clear_texture(white, wgpu::ImageSubresourceRange::default())
+            .finish();
+        graph
+            .add_pass("SSAO", NodeType::RenderPass)
+            .write(ao)
+            .enabled_if(|| false)
+            .fallback(ao, white)
+            .execute(|_| {});
+        graph.add_pass("Lighting", NodeType::RenderPass).read(ao).write(hdr).execute(|_| {});
+        // written but never read
+        graph.add_pass("Overlay", NodeType::RenderPass).write(overlay).execute(|_| {});
+        // writes nothing, kept for its side effects
+        graph.add_pass("Stats", NodeType::ComputePass).execute(|_| {});
+
+        let commands = record(&mut graph);
+        assert_eq!(graph.culled_nodes(), ["SSAO", "Overlay"]);
+        let mut kept = node_labels(&graph);
+        kept.sort();
+        assert_eq!(kept, ["Clear AO", "Lighting", "Stats"]);
+
+        let plan = graph.execution_plan().unwrap();
+        assert!(plan.resource(ao).is_none());
+        assert!(plan.resource(overlay).is_none());
+        assert!(commands.contains(&RecordedCommand::ClearTexture { target: "White".into() }));
+    }
+
+    #[test]
+    fn keeps_every_node_until_an_output_is_marked() {
+        let mut graph = RenderGraph::new();
+        let hdr = add_target(&mut graph, "HDR");
+        let overlay = add_target(&mut graph, "Overlay");
+        // published for other features, but not a result of the frame
+        graph.blackboard_mut().insert("overlay", overlay);
+
+        graph.add_pass("Lighting", NodeType::RenderPass).write(hdr).execute(|_| {});
+        graph.add_pass("Overlay", NodeType::RenderPass).write(overlay).execute(|_| {});
+
+        record(&mut graph);
+        assert!(graph.culled_nodes().is_empty());
+
+        graph.mark_output(hdr);
+        record(&mut graph);
+        assert_eq!(graph.culled_nodes(), ["Overlay"]);
+    }
+
+    #[test]
+    fn subgraph_transients_are_private_to_their_instance() {
+        let mut graph = RenderGraph::new();
+        let hdr = add_target(&mut graph, "HDR");
+        let output = add_target(&mut graph, "Output");
+        graph.mark_output(output);
+
+        let bloom = |graph: &mut RenderGraph| {
+            let half = add_target(graph, "Half");
+            graph.add_pass("Downsample", NodeType::RenderPass).read(hdr).write(half).execute(|_| {});
+            half
+        };
+        let near = graph.subgraph("Near", bloom);
+        let far = graph.subgraph("Far", bloom);
+        assert_eq!(graph.resource_label(near), "Near/Half");
+        assert_eq!(graph.resource_label(far), "Far/Half");
+
+        // not returned, so it stays private to the instance
+        let mut private = None;
+        graph.subgraph("Leaky", |graph| {
+            let scratch = add_target(graph, "Scratch");
+            graph.add_pass("Fill", NodeType::RenderPass).write(scratch).execute(|_| {});
+            private = Some(scratch);
+        });
+
+        graph
+            .add_pass("Composite", NodeType::RenderPass)
+            .read(near)
+            .read(far)
+            .read(private.unwrap())
+            .write(output)
+            .execute(|_| {});
+        assert!(matches!(
+            graph.plan(),
+            Err(GraphError::ScopeViolation { node, resource }) if node == "Composite" && resource == "Leaky/Scratch"
+        ));
+    }
+
+    fn add_eyes(graph: &mut RenderGraph, name: &str, format: wgpu::TextureFormat) -> ResourceHandle {
+        graph.add_texture(
+            name,
+            TextureDesc {
+                size: wgpu::Extent3d { width: 64, height: 64, depth_or_array_layers: 2 },
+                dimension: wgpu::TextureDimension::D2,
+                format,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            },
+        )
+    }
+
+    #[test]
+    fn renders_both_eyes_in_one_pass_and_copies_them_side_by_side() {
+        let mut graph = RenderGraph::new();
+        let eyes = add_eyes(&mut graph, "Eyes", wgpu::TextureFormat::Rgba8Unorm);
+        let eye_depth = add_eyes(&mut graph, "Eye depth", wgpu::TextureFormat::Depth32Float);
+        let preview = graph.add_texture(
+            "Preview",
+            TextureDesc {
+                size: wgpu::Extent3d { width: 128, height: 64, depth_or_array_layers: 1 },
+                dimension: wgpu::TextureDimension::D2,
+                format: wgpu::TextureFormat::Rgba8Unorm,
+                usage: wgpu::TextureUsages::COPY_DST | wgpu::TextureUsages::TEXTURE_BINDING,
+            },
+        );
+        graph.mark_output(preview);
+
+        graph
+            .add_pass("Stereo", NodeType::RenderPass)
+            .write(eyes)
+            .write_depth(eye_depth)
+            .multiview(2)
+            .execute(|_| {});
+        side_by_side(&mut graph, "Side by side", eyes, preview);
+
+        let commands = record(&mut graph);
+        assert_eq!(node_labels(&graph), ["Stereo", "Side by side"]);
+        assert!(commands.iter().any(|command| matches!(
+            command,
+            RecordedCommand::BeginRenderPass { label, multiview_mask, .. }
+                if label == "Stereo" && *multiview_mask == NonZeroU32::new(0b11)
+        )));
+        let copies = commands
+            .iter()
+            .filter(|command| {
+                matches!(command, RecordedCommand::CopyTexture { src, dst, .. } if src == "Eyes" && dst == "Preview")
+            })
+            .count();
+        assert_eq!(copies, 2);
+
+        // layer n lands n eye widths to the right
+        let copy = graph.nodes.iter().find(|node| node.name == "Side by side").unwrap();
+        let regions: Vec<_> = copy
+            .transfer
+            .as_ref()
+            .unwrap()
+            .texture_copy
+            .iter()
+            .map(|op| (op.region.array_layer, op.dst_region.origin.x, op.dst_region.extent.width))
+            .collect();
+        assert_eq!(regions, [(0, 0, 64), (1, 64, 64)]);
+    }
+
+    #[test]
+    fn rejects_multiview_attachments_without_a_layer_per_view() {
+        let mut graph = RenderGraph::new();
+        let eyes = add_eyes(&mut graph, "Eyes", wgpu::TextureFormat::Rgba8Unorm);
+        let depth = add_target(&mut graph, "Depth");
+        graph.add_pass("Stereo", NodeType::RenderPass).write(eyes).write_depth(depth).multiview(2).execute(|_| {});
+
+        let error = graph.plan().unwrap_err();
+        assert!(
+            matches!(
+                &error,
+                GraphError::MultiviewLayers { node, resource, layers: 1, views: 2 }
+                    if node == "Stereo" && resource == "Depth"
+            ),
+            "{error}"
+        );
+    }
+
+    #[test]
+    #[ignore = "needs a GPU adapter"]
+    fn rejects_pipelines_created_for_other_views() {
+        let (device, _queue) = test_device();
+        let mut resources = Resources::new(Arc::new(device));
+        let shader = resources.load_shader_stages("base").unwrap();
+        let layout = resources.create_reflected_layout(None, &[shader.vertex, shader.fragment]).unwrap();
+        // created without a multiview mask, so for one view
+        let pipeline = resources
+            .create_render_pipeline(RenderPipelineDesc {
+                label: Some("Triangle"),
+                layout,
+                vertex: VertexState { module: shader.vertex, entry_point: None, buffers: &[], constants: &[] },
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+                fragment: Some(FragmentState {
+                    module: shader.fragment,
+                    entry_point: None,
+                    targets: &[Some(wgpu::TextureFormat::Rgba8Unorm.into())],
+                    constants: &[],
+                }),
+                multiview_mask: None,
+            })
+            .unwrap();
+
+        let mut graph = RenderGraph::new();
+        let eyes = add_eyes(&mut graph, "Eyes", wgpu::TextureFormat::Rgba8Unorm);
+        graph
+            .add_pass("Stereo", NodeType::RenderPass)
+            .write(eyes)
+            .multiview(2)
+            .use_pipeline(PipelineHandle::Render(pipeline))
+            .execute(|_| {});
+        graph.plan().unwrap();
+
+        let error = graph.validate_pipelines(&resources).unwrap_err();
+        assert!(
+            matches!(
+                &error,
+                GraphError::MultiviewMismatch { node, pass, pipeline: None }
+                    if node == "Stereo" && *pass == NonZeroU32::new(0b11)
+            ),
+            "{error}"
+        );
+    }
+
+    #[test]
+    fn only_the_first_view_clears_a_shared_target() {
+        let mut graph = RenderGraph::new();
+        let color = add_target(&mut graph, "Color");
+        let depth = graph.add_texture(
+            "Depth",
+            TextureDesc {
+                size: wgpu::Extent3d { width: 64, height: 64, depth_or_array_layers: 1 },
+                dimension: wgpu::TextureDimension::D2,
+                format: wgpu::TextureFormat::Depth32Float,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            },
+        );
+        graph.mark_output(color);
+
+        let view = |name: &str, x: u32| View {
+            name: name.into(),
+            uniforms: ViewUniforms::zeroed(),
+            target: color,
+            viewport: Some(Viewport::new(x, 0, 32, 64)),
+        };
+        graph.add_views(&[view("Left", 0), view("Right", 32)], |graph, ctx| {
+            graph
+                .add_pass("Opaque", NodeType::RenderPass)
+                .write(ctx.view.target)
+                .write_depth(depth)
+                .bind(0, 0, ctx.uniforms)
+                .execute(|_| {});
+        });
+
+        let commands = record(&mut graph);
+        let passes: Vec<(String, Vec<String>)> = commands
+            .iter()
+            .filter_map(|command| match command {
+                RecordedCommand::BeginRenderPass { label, cleared, .. } => Some((label.clone(), cleared.clone())),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            passes,
+            [
+                ("Left/Opaque".to_string(), vec!["Color".to_string(), "Depth".to_string()]),
+                ("Right/Opaque".to_string(), vec![]),
+            ]
+        );
+        assert!(commands.contains(&RecordedCommand::SetViewport(Viewport::new(32, 0, 32, 64))));
+    }
+
+    #[test]
+    fn reuses_the_plan_while_the_structure_is_unchanged() {
+        let build = |graph: &mut RenderGraph, width: u32| {
+            graph.begin_frame();
+            let hdr = graph.add_texture(
+                "HDR",
+                TextureDesc {
+                    size: wgpu::Extent3d { width, height: 64, depth_or_array_layers: 1 },
+                    dimension: wgpu::TextureDimension::D2,
+                    format: wgpu::TextureFormat::Rgba16Float,
+                    usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+                },
+            );
+            graph.mark_output(hdr);
+            graph.add_pass("Sky", NodeType::RenderPass).write(hdr).execute(|_| {});
+        };
+
+        let mut graph = RenderGraph::new();
+        build(&mut graph, 64);
+        let hash = graph.plan().unwrap().hash;
+        assert!(!graph.compile_stats().cache_hit);
+
+        // planning the same graph again, or an identical next frame, reuses the plan
+        graph.plan().unwrap();
+        assert!(graph.compile_stats().cache_hit);
+        build(&mut graph, 64);
+        assert_eq!(graph.plan().unwrap().hash, hash);
+        assert!(graph.compile_stats().cache_hit);
+
+        // a resized target changes the structure
+        build(&mut graph, 128);
+        assert_ne!(graph.plan().unwrap().hash, hash);
+        assert!(!graph.compile_stats().cache_hit);
+    }
+
+    #[test]
+    fn pads_buffer_writes_to_four_bytes() {
+        let mut graph = RenderGraph::new();
+        let counters = graph.add_buffer(
+            "Counters",
+            BufferDesc { size: 16, usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST, mapped_at_creation: false },
+        );
+        graph.mark_output(counters);
+        graph.add_transfer("Upload").write(counters, 4, vec![1u8, 2, 3]).finish();
+
+        let commands = record(&mut graph);
+        assert!(commands.contains(&RecordedCommand::WriteBuffer { target: "Counters".into(), offset: 4, size: 4 }));
+    }
+
+    #[test]
+    fn fills_zeroes_with_a_clear() {
+        let mut graph = RenderGraph::new();
+        let bins = graph.add_buffer(
+            "Bins",
+            BufferDesc { size: 16, usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST, mapped_at_creation: false },
+        );
+        graph.mark_output(bins);
+        graph
+            .add_transfer("Reset")
+            .fill_buffer(bins, 0, 0u8, 3)
+            .fill_buffer(bins, 8, 1u8, 3)
+            .finish();
+
+        let commands = record(&mut graph);
+        assert!(commands.contains(&RecordedCommand::ClearBuffer { target: "Bins".into(), offset: 0, size: Some(4) }));
+        assert!(commands.contains(&RecordedCommand::WriteBuffer { target: "Bins".into(), offset: 8, size: 4 }));
+    }
+
+    #[test]
+    #[should_panic(expected = "needs 256 bytes of data")]
+    fn rejects_texture_uploads_shorter_than_the_region() {
+        let mut graph = RenderGraph::new();
+        let lut = add_target(&mut graph, "LUT");
+        let extent = wgpu::Extent3d { width: 8, height: 8, depth_or_array_layers: 1 };
+        let layout = wgpu::TexelCopyBufferLayout { offset: 0, bytes_per_row: Some(32), rows_per_image: None };
+        graph.add_transfer("Upload LUT").write_texture(lut, TextureRegion::new(extent), layout, &[0u8; 255]);
+    }
+
+    #[test]
+    fn lifetimes_span_first_to_last_use() {
+        let mut graph = RenderGraph::new();
+        let depth = add_target(&mut graph, "Depth");
+        let ao = add_target(&mut graph, "AO");
+        let hdr = add_target(&mut graph, "HDR");
+        graph.mark_output(hdr);
+
+        graph.add_pass("Prepass", NodeType::RenderPass).write(depth).execute(|_| {});
+        graph.add_pass("SSAO", NodeType::ComputePass).read(depth).write(ao).execute(|_| {});
+        graph.add_pass("Lighting", NodeType::RenderPass).read(depth).read(ao).write(hdr).execute(|_| {});
+        graph.plan().unwrap();
+
+        assert_eq!(graph.execution_order(), Some([0, 1, 2].as_slice()));
+        let lifetime = |resource| graph.resource_lifetime(resource).unwrap();
+        assert_eq!(lifetime(depth), ResourceLifetime { first_use: 0, last_use: 2 });
+        assert_eq!(lifetime(ao), ResourceLifetime { first_use: 1, last_use: 2 });
+        assert_eq!(lifetime(hdr), ResourceLifetime { first_use: 2, last_use: 2 });
+
+        // no aliasing yet: every resource has an allocation of its own
+        let allocations: HashSet<_> = [depth, ao, hdr]
+            .into_iter()
+            .map(|resource| graph.physical_allocation(resource).unwrap())
+            .collect();
+        assert_eq!(allocations.len(), 3);
+    }
+}
@@ -1,5 +1,15 @@
+pub mod blackboard;
+pub mod executor;
+pub mod feature;
 pub mod graph;
+pub mod plan;
+pub mod schedule;
+pub mod subgraph;
 pub mod types;
+pub mod view;
 
+pub mod preprocessor;
+pub mod reflection;
 pub mod registry;
 pub mod resource_pool;
+pub mod transient_pool;
@@ -0,0 +1,121 @@
+use std::collections::HashMap;
+use std::num::NonZeroU32;
+
+use serde::Serialize;
+
+use crate::gpu::render_graph::schedule::ScheduleReport;
+use crate::gpu::render_graph::types::{BufferDesc, NodeBinding, NodeType, PipelineHandle, ResourceHandle, TextureDesc, Viewport};
+
+/// Output of the planning phase of `RenderGraph::compile`: everything decided about a frame
+/// without a GPU. Executed by a `GraphExecutor`.
+#[derive(Serialize, Clone, Debug)]
+pub struct ExecutionPlan {
+    // structure hash of the graph this plan was built from
+    pub hash: u64,
+    pub schedule: ScheduleReport,
+    pub resources: Vec<PlannedResource>,
+    pub steps: Vec<PlanStep>,
+    // labels of nodes culled because they were disabled or unused
+    pub culled: Vec<String>,
+
+    #[serde(skip)]
+    resource_index: HashMap<ResourceHandle, usize>,
+}
+
+impl ExecutionPlan {
+    pub fn new(
+        hash: u64,
+        schedule: ScheduleReport,
+        resources: Vec<PlannedResource>,
+        steps: Vec<PlanStep>,
+        culled: Vec<String>,
+    ) -> Self {
+        let resource_index = resources
+            .iter()
+            .enumerate()
+            .map(|(idx, planned)| (planned.resource, idx))
+            .collect();
+
+        ExecutionPlan {
+            hash,
+            schedule,
+            resources,
+            steps,
+            culled,
+            resource_index,
+        }
+    }
+
+    pub fn resource(&self, resource: ResourceHandle) -> Option<&PlannedResource> {
+        self.resource_index.get(&resource).map(|&idx| &self.resources[idx])
+    }
+
+    pub fn label(&self, resource: ResourceHandle) -> &str {
+        self.resource(resource).map_or("Unnamed resource", |planned| planned.label.as_str())
+    }
+
+    pub fn texture_desc(&self, resource: ResourceHandle) -> &TextureDesc {
+        match self.resource(resource).map(|planned| &planned.desc) {
+            Some(ResourceDesc::Texture(desc)) => desc,
+            _ => panic!("{} is not a planned texture", self.label(resource)),
+        }
+    }
+
+    /// Planned nodes in execution order.
+    pub fn nodes(&self) -> impl Iterator<Item = &PlannedNode> {
+        self.steps.iter().filter_map(|step| match step {
+            PlanStep::Node(node) => Some(node),
+            _ => None,
+        })
+    }
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub enum PlanStep {
+    PushDebugGroup(String),
+    PopDebugGroup,
+    Node(PlannedNode),
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct PlannedNode {
+    // index into the graph's node list
+    pub node: usize,
+    pub label: String,
+    pub kind: NodeType,
+    pub pipeline: Option<PipelineHandle>,
+    // resources as declared on the node, bindings included; depth counts as a write
+    pub reads: Vec<ResourceHandle>,
+    pub writes: Vec<ResourceHandle>,
+    pub color_attachments: Vec<ResourceHandle>,
+    pub depth_attachment: Option<ResourceHandle>,
+    pub viewport: Option<Viewport>,
+    pub multiview_mask: Option<NonZeroU32>,
+    // attachments no earlier step touched; cleared on load, the others are loaded
+    pub cleared: Vec<ResourceHandle>,
+    // sorted by group, then binding
+    pub bindings: Vec<NodeBinding>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct PlannedResource {
+    pub resource: ResourceHandle,
+    pub label: String,
+    pub desc: ResourceDesc,
+    pub lifetime: ResourceLifetime,
+    // physical allocation backing the resource; every resource has its own, nothing is aliased
+    pub allocation: usize,
+}
+
+#[derive(Serialize, Clone, Copy, Debug)]
+pub enum ResourceDesc {
+    Texture(TextureDesc),
+    Buffer(BufferDesc),
+}
+
+/// First and last execution step (position in the execution order) using a resource.
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ResourceLifetime {
+    pub first_use: u32,
+    pub last_use: u32,
+}
@@ -0,0 +1,266 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// WGSL after `#include`s and conditionals were resolved, with where each line came from.
+#[derive(Clone, Debug)]
+pub struct PreprocessedSource {
+    pub code: String,
+    // the root file first, then includes in the order they were first read
+    files: Vec<PathBuf>,
+    // per output line: index into `files` and 1-based line in that file
+    line_map: Vec<(usize, u32)>,
+}
+
+impl PreprocessedSource {
+    /// Every file the source was built from, to watch for changes.
+    pub fn files(&self) -> &[PathBuf] {
+        &self.files
+    }
+
+    /// Original file and line of the 1-based output `line`.
+    pub fn location(&self, line: u32) -> Option<(&Path, u32)> {
+        let &(file, original) = self.line_map.get((line as usize).checked_sub(1)?)?;
+        Some((&self.files[file], original))
+    }
+
+    /// Rewrites the `wgsl:line:column` locations naga puts in its messages into the original
+    /// `file:line:column`.
+    pub fn map_locations(&self, message: &str) -> String {
+        const MARKER: &str = "wgsl:";
+
+        let mut mapped = String::with_capacity(message.len());
+        let mut rest = message;
+        while let Some(start) = rest.find(MARKER) {
+            mapped.push_str(&rest[..start]);
+            let after = &rest[start + MARKER.len()..];
+            let digits = after.find(|c: char| !c.is_ascii_digit()).unwrap_or(after.len());
+
+            match after[..digits].parse().ok().and_then(|line| self.location(line)) {
+                Some((file, line)) => {
+                    mapped.push_str(&format!("{}:{line}", file.display()));
+                    rest = &after[digits..];
+                }
+                None => {
+                    mapped.push_str(MARKER);
+                    rest = after;
+                }
+            }
+        }
+        mapped.push_str(rest);
+        mapped
+    }
+}
+
+/// A directive that could not be processed, at its place in the original files.
+#[derive(Debug)]
+pub struct PreprocessError {
+    pub file: PathBuf,
+    pub line: u32,
+    pub message: String,
+}
+
+impl fmt::Display for PreprocessError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.file.display(), self.line, self.message)
+    }
+}
+
+impl std::error::Error for PreprocessError {}
+
+/// Resolves `#include "file"`, `#define NAME [value]`, `#undef`, `#ifdef`/`#ifndef`/`#else`/
+/// `#endif` and `#pragma once` in WGSL. Includes are looked up next to the including file,
+/// then in each include root. Defines with a value replace the name wherever it appears as a
+/// whole identifier. Defines are shared by all files, so classic include guards work.
+pub struct Preprocessor<'a> {
+    include_roots: &'a [PathBuf],
+    defines: HashMap<String, String>,
+    files: Vec<PathBuf>,
+    once: Vec<usize>,
+    stack: Vec<usize>,
+    code: String,
+    line_map: Vec<(usize, u32)>,
+}
+
+// state of one `#ifdef`/`#ifndef` block
+struct Conditional {
+    // whether the enclosing block emits lines
+    parent_active: bool,
+    taken: bool,
+    in_else: bool,
+}
+
+impl<'a> Preprocessor<'a> {
+    pub fn new(include_roots: &'a [PathBuf]) -> Self {
+        Preprocessor {
+            include_roots,
+            defines: HashMap::new(),
+            files: Vec::new(),
+            once: Vec::new(),
+            stack: Vec::new(),
+            code: String::new(),
+            line_map: Vec::new(),
+        }
+    }
+
+    pub fn define(mut self, name: &str, value: &str) -> Self {
+        self.defines.insert(name.to_string(), value.to_string());
+        self
+    }
+
+    pub fn run(mut self, path: &Path) -> Result<PreprocessedSource, PreprocessError> {
+        let source = std::fs::read_to_string(path).map_err(|error| PreprocessError {
+            file: path.to_path_buf(),
+            line: 0,
+            message: format!("Failed to read shader file: {error}"),
+        })?;
+        self.process(path.to_path_buf(), &source)?;
+
+        Ok(PreprocessedSource {
+            code: self.code,
+            files: self.files,
+            line_map: self.line_map,
+        })
+    }
+
+    fn process(&mut self, path: PathBuf, source: &str) -> Result<(), PreprocessError> {
+        let file = match self.files.iter().position(|known| *known == path) {
+            Some(file) => file,
+            None => {
+                self.files.push(path);
+                self.files.len() - 1
+            }
+        };
+        if self.once.contains(&file) {
+            return Ok(());
+        }
+        self.stack.push(file);
+        let path = self.files[file].clone();
+
+        let mut conditionals: Vec<Conditional> = Vec::new();
+        let mut last_line = 0;
+        for (index, line) in source.lines().enumerate() {
+            let number = index as u32 + 1;
+            last_line = number;
+            let active = conditionals.last().is_none_or(|block| block.parent_active && block.taken != block.in_else);
+            let error = |message: String| PreprocessError {
+                file: path.clone(),
+                line: number,
+                message,
+            };
+
+            let Some(directive) = line.trim_start().strip_prefix('#') else {
+                if active {
+                    self.emit(file, number, line);
+                }
+                continue;
+            };
+            let mut words = directive.split_whitespace();
+            let keyword = words.next().unwrap_or_default();
+            let argument = words.next();
+
+            match keyword {
+                "ifdef" | "ifndef" => {
+                    let name = argument.ok_or_else(|| error(format!("#{keyword} needs a name")))?;
+                    let defined = self.defines.contains_key(name);
+                    conditionals.push(Conditional {
+                        parent_active: active,
+                        taken: defined == (keyword == "ifdef"),
+                        in_else: false,
+                    });
+                }
+                "else" => {
+                    let block = conditionals.last_mut().ok_or_else(|| error("#else without #ifdef".to_string()))?;
+                    if block.in_else {
+                        return Err(error("Second #else in the same block".to_string()));
+                    }
+                    block.in_else = true;
+                }
+                "endif" => {
+                    conditionals.pop().ok_or_else(|| error("#endif without #ifdef".to_string()))?;
+                }
+                _ if !active => {}
+                "define" => {
+                    let name = argument.ok_or_else(|| error("#define needs a name".to_string()))?;
+                    let value = words.collect::<Vec<_>>().join(" ");
+                    self.defines.insert(name.to_string(), value);
+                }
+                "undef" => {
+                    let name = argument.ok_or_else(|| error("#undef needs a name".to_string()))?;
+                    self.defines.remove(name);
+                }
+                "pragma" if argument == Some("once") => self.once.push(file),
+                "include" => {
+                    let name = directive.trim_start()["include".len()..].trim();
+                    let name = name
+                        .strip_prefix('"')
+                        .and_then(|name| name.strip_suffix('"'))
+                        .ok_or_else(|| error(format!("Expected #include \"file\", found #include {name}")))?;
+                    let included = self.resolve_include(file, name).map_err(error)?;
+
+                    let included_file = self.files.iter().position(|known| *known == included);
+                    if included_file.is_some_and(|included_file| self.stack.contains(&included_file)) {
+                        return Err(error(format!("{name} includes itself")));
+                    }
+                    let source = std::fs::read_to_string(&included)
+                        .map_err(|read_error| error(format!("Failed to read {}: {read_error}", included.display())))?;
+                    self.process(included, &source)?;
+                }
+                _ => return Err(error(format!("Unknown directive #{keyword}"))),
+            }
+        }
+
+        if !conditionals.is_empty() {
+            return Err(PreprocessError {
+                file: path,
+                line: last_line,
+                message: format!("{} #ifdef block(s) not closed by #endif", conditionals.len()),
+            });
+        }
+        self.stack.pop();
+        Ok(())
+    }
+
+    fn resolve_include(&self, file: usize, name: &str) -> Result<PathBuf, String> {
+        let next_to = self.files[file].parent().map(|directory| directory.join(name));
+        let candidates: Vec<PathBuf> = next_to
+            .into_iter()
+            .chain(self.include_roots.iter().map(|root| root.join(name)))
+            .collect();
+
+        match candidates.iter().find(|path| path.is_file()) {
+            Some(path) => Ok(path.clone()),
+            None => {
+                let searched: Vec<String> = candidates.iter().map(|path| path.display().to_string()).collect();
+                Err(format!("Include \"{name}\" not found, searched: {}", searched.join(", ")))
+            }
+        }
+    }
+
+    fn emit(&mut self, file: usize, number: u32, line: &str) {
+        let mut rest = line;
+        while let Some(start) = rest.find(is_identifier_start) {
+            self.code.push_str(&rest[..start]);
+            let identifier = &rest[start..];
+            let end = identifier.find(|c: char| !is_identifier_char(c)).unwrap_or(identifier.len());
+            let identifier = &identifier[..end];
+
+            match self.defines.get(identifier) {
+                Some(value) if !value.is_empty() => self.code.push_str(value),
+                _ => self.code.push_str(identifier),
+            }
+            rest = &rest[start + end..];
+        }
+        self.code.push_str(rest);
+        self.code.push('\n');
+        self.line_map.push((file, number));
+    }
+}
+
+fn is_identifier_start(c: char) -> bool {
+    c.is_alphabetic() || c == '_'
+}
+
+fn is_identifier_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
@@ -0,0 +1,437 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::num::NonZeroU64;
+
+// the naga wgpu itself uses, so the parsed module matches what the device compiles
+use wgpu::naga;
+
+/// A `@group/@binding` resource of a shader and the stages of the entry points using it.
+#[derive(Clone, Debug)]
+pub struct ReflectedBinding {
+    pub group: u32,
+    pub binding: u32,
+    pub name: Option<String>,
+    pub ty: wgpu::BindingType,
+    pub count: Option<std::num::NonZeroU32>,
+    pub visibility: wgpu::ShaderStages,
+}
+
+#[derive(Clone, Debug)]
+pub struct ReflectedEntryPoint {
+    pub name: String,
+    pub stage: wgpu::ShaderStages,
+}
+
+/// A WGSL `override` declaration; pipelines set it by name, or by `@id` when it has one.
+#[derive(Clone, Debug)]
+pub struct ReflectedOverride {
+    pub name: Option<String>,
+    pub id: Option<u16>,
+}
+
+/// Numeric type a vertex input is read as; attribute formats have to match it.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum VertexInputKind {
+    Float,
+    Sint,
+    Uint,
+}
+
+/// A `@location` input of a vertex entry point.
+#[derive(Clone, Debug)]
+pub struct VertexInput {
+    pub entry_point: String,
+    pub location: u32,
+    pub name: Option<String>,
+    pub kind: VertexInputKind,
+}
+
+/// What a WGSL module declares, as far as pipeline creation cares.
+#[derive(Clone, Debug, Default)]
+pub struct ShaderReflection {
+    pub bindings: Vec<ReflectedBinding>,
+    pub entry_points: Vec<ReflectedEntryPoint>,
+    pub vertex_inputs: Vec<VertexInput>,
+    pub overrides: Vec<ReflectedOverride>,
+}
+
+/// A parse or validation error of naga. Lines and columns are 1-based and refer to the
+/// source that was reflected; the column counts bytes.
+#[derive(Clone, Debug)]
+pub struct SourceDiagnostic {
+    pub message: String,
+    // what the marked span is, e.g. "invalid accessor"
+    pub label: Option<String>,
+    pub line: Option<u32>,
+    pub column: u32,
+    pub length: u32,
+}
+
+impl SourceDiagnostic {
+    fn new(message: String, label: Option<String>, location: Option<naga::SourceLocation>) -> Self {
+        SourceDiagnostic {
+            message,
+            label,
+            line: location.map(|location| location.line_number),
+            column: location.map_or(0, |location| location.line_position),
+            length: location.map_or(0, |location| location.length),
+        }
+    }
+}
+
+impl fmt::Display for SourceDiagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "{} at {line}:{}", self.message, self.column),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ReflectionError {
+    Parse(SourceDiagnostic),
+    Validation(SourceDiagnostic),
+    BindingConflict { group: u32, binding: u32, name: Option<String> },
+    MissingVertexAttribute { location: u32, entry_point: String, name: Option<String> },
+    VertexFormatMismatch { location: u32, format: wgpu::VertexFormat, input: VertexInputKind, name: Option<String> },
+}
+
+impl fmt::Display for ReflectionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReflectionError::Parse(diagnostic) => write!(f, "Failed to parse shader: {diagnostic}"),
+            ReflectionError::Validation(diagnostic) => write!(f, "Shader failed validation: {diagnostic}"),
+            ReflectionError::BindingConflict { group, binding, name } => write!(
+                f,
+                "@group({group}) @binding({binding}){} is declared with different types across the shaders",
+                quoted(name)
+            ),
+            ReflectionError::MissingVertexAttribute { location, entry_point, name } => write!(
+                f,
+                "Vertex input{} @location({location}) of '{entry_point}' is not provided by any vertex buffer",
+                quoted(name)
+            ),
+            ReflectionError::VertexFormatMismatch { location, format, input, name } => write!(
+                f,
+                "Vertex attribute {format:?} at @location({location}) does not match the type of shader input{} ({input:?})",
+                quoted(name)
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ReflectionError {}
+
+// " 'name'" for the messages, nothing for unnamed declarations
+fn quoted(name: &Option<String>) -> String {
+    name.as_ref().map_or(String::new(), |name| format!(" '{name}'"))
+}
+
+impl ShaderReflection {
+    /// Reflects a preprocessed WGSL source.
+    pub fn from_wgsl(source: &str) -> Result<Self, ReflectionError> {
+        let module = naga::front::wgsl::parse_str(source).map_err(|error| {
+            let label = error.labels().next().map(|(_, label)| label.to_string());
+            ReflectionError::Parse(SourceDiagnostic::new(error.message().to_string(), label, error.location(source)))
+        })?;
+        let info = naga::valid::Validator::new(naga::valid::ValidationFlags::all(), naga::valid::Capabilities::all())
+            .validate(&module)
+            .map_err(|error| {
+                // the validation error alone is often just "Function [1] 'main' is invalid"
+                let mut message = error.as_inner().to_string();
+                let mut cause = std::error::Error::source(error.as_inner());
+                while let Some(inner) = cause {
+                    message.push_str(&format!(": {inner}"));
+                    cause = inner.source();
+                }
+                let label = error.spans().next().map(|(_, label)| label.clone()).filter(|label| !label.is_empty());
+                ReflectionError::Validation(SourceDiagnostic::new(message, label, error.location(source)))
+            })?;
+
+        let entry_points: Vec<ReflectedEntryPoint> = module
+            .entry_points
+            .iter()
+            .map(|entry_point| ReflectedEntryPoint {
+                name: entry_point.name.clone(),
+                stage: shader_stage(entry_point.stage),
+            })
+            .collect();
+
+        let mut bindings = Vec::new();
+        for (handle, global) in module.global_variables.iter() {
+            let Some(resource) = &global.binding else { continue; };
+            let Some((ty, count)) = binding_type(&module, global) else { continue; };
+
+            let visibility = module
+                .entry_points
+                .iter()
+                .enumerate()
+                .filter(|&(index, _)| !info.get_entry_point(index)[handle].is_empty())
+                .fold(wgpu::ShaderStages::NONE, |visibility, (_, entry_point)| {
+                    visibility | shader_stage(entry_point.stage)
+                });
+
+            bindings.push(ReflectedBinding {
+                group: resource.group,
+                binding: resource.binding,
+                name: global.name.clone(),
+                ty,
+                count,
+                visibility,
+            });
+        }
+
+        let mut vertex_inputs = Vec::new();
+        for entry_point in module.entry_points.iter().filter(|entry_point| entry_point.stage == naga::ShaderStage::Vertex) {
+            for argument in &entry_point.function.arguments {
+                match (&argument.binding, &module.types[argument.ty].inner) {
+                    (Some(binding), inner) => {
+                        vertex_inputs.extend(vertex_input(&entry_point.name, binding, argument.name.clone(), inner));
+                    }
+                    (None, naga::TypeInner::Struct { members, .. }) => {
+                        for member in members {
+                            let Some(binding) = &member.binding else { continue; };
+                            let inner = &module.types[member.ty].inner;
+                            vertex_inputs.extend(vertex_input(&entry_point.name, binding, member.name.clone(), inner));
+                        }
+                    }
+                    (None, _) => {}
+                }
+            }
+        }
+
+        let overrides = module
+            .overrides
+            .iter()
+            .map(|(_, declaration)| ReflectedOverride { name: declaration.name.clone(), id: declaration.id })
+            .collect();
+
+        Ok(ShaderReflection {
+            bindings,
+            entry_points,
+            vertex_inputs,
+            overrides,
+        })
+    }
+
+    /// Whether the module declares the override a pipeline constant `key` sets: its name, or
+    /// its `@id` as a decimal number.
+    pub fn declares_override(&self, key: &str) -> bool {
+        self.overrides.iter().any(|declaration| {
+            declaration.name.as_deref() == Some(key) || declaration.id.is_some_and(|id| id.to_string() == key)
+        })
+    }
+
+    /// Every binding of `reflections`, grouped by `@group` and sorted by `@binding`. Bindings
+    /// declared by several shaders are visible to the stages of all of them.
+    pub fn merge_bindings<'a>(
+        reflections: impl IntoIterator<Item = &'a ShaderReflection>,
+    ) -> Result<BTreeMap<u32, Vec<ReflectedBinding>>, ReflectionError> {
+        let mut groups: BTreeMap<u32, Vec<ReflectedBinding>> = BTreeMap::new();
+        for binding in reflections.into_iter().flat_map(|reflection| &reflection.bindings) {
+            let group = groups.entry(binding.group).or_default();
+            match group.iter_mut().find(|existing| existing.binding == binding.binding) {
+                Some(existing) if existing.ty != binding.ty || existing.count != binding.count => {
+                    return Err(ReflectionError::BindingConflict {
+                        group: binding.group,
+                        binding: binding.binding,
+                        name: binding.name.clone(),
+                    });
+                }
+                Some(existing) => existing.visibility |= binding.visibility,
+                None => group.push(binding.clone()),
+            }
+        }
+
+        for group in groups.values_mut() {
+            group.sort_by_key(|binding| binding.binding);
+        }
+        Ok(groups)
+    }
+
+    /// Checks that `buffers` provide every input of the vertex entry points, with attribute
+    /// formats of the same numeric type.
+    pub fn check_vertex_buffers(&self, buffers: &[wgpu::VertexBufferLayout]) -> Result<(), ReflectionError> {
+        for input in &self.vertex_inputs {
+            let attribute = buffers
+                .iter()
+                .flat_map(|buffer| buffer.attributes)
+                .find(|attribute| attribute.shader_location == input.location)
+                .ok_or_else(|| ReflectionError::MissingVertexAttribute {
+                    location: input.location,
+                    entry_point: input.entry_point.clone(),
+                    name: input.name.clone(),
+                })?;
+
+            if vertex_format_kind(attribute.format) != input.kind {
+                return Err(ReflectionError::VertexFormatMismatch {
+                    location: input.location,
+                    format: attribute.format,
+                    input: input.kind,
+                    name: input.name.clone(),
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+fn shader_stage(stage: naga::ShaderStage) -> wgpu::ShaderStages {
+    match stage {
+        naga::ShaderStage::Vertex => wgpu::ShaderStages::VERTEX,
+        naga::ShaderStage::Fragment => wgpu::ShaderStages::FRAGMENT,
+        naga::ShaderStage::Compute => wgpu::ShaderStages::COMPUTE,
+        naga::ShaderStage::Task => wgpu::ShaderStages::TASK,
+        naga::ShaderStage::Mesh => wgpu::ShaderStages::MESH,
+    }
+}
+
+fn binding_type(
+    module: &naga::Module,
+    global: &naga::GlobalVariable,
+) -> Option<(wgpu::BindingType, Option<std::num::NonZeroU32>)> {
+    let (inner, count) = match &module.types[global.ty].inner {
+        naga::TypeInner::BindingArray { base, size } => {
+            let count = match size {
+                naga::ArraySize::Constant(count) => Some(*count),
+                _ => None,
+            };
+            (&module.types[*base].inner, count)
+        }
+        inner => (inner, None),
+    };
+
+    let ty = match global.space {
+        naga::AddressSpace::Uniform => wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Uniform,
+            has_dynamic_offset: false,
+            min_binding_size: NonZeroU64::new(inner.size(module.to_ctx()) as u64),
+        },
+        naga::AddressSpace::Storage { access } => wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Storage {
+                read_only: !access.contains(naga::StorageAccess::STORE),
+            },
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        },
+        naga::AddressSpace::Handle => match *inner {
+            naga::TypeInner::Sampler { comparison: true } => {
+                wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Comparison)
+            }
+            naga::TypeInner::Sampler { comparison: false } => {
+                wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering)
+            }
+            naga::TypeInner::Image { dim, arrayed, class } => {
+                let view_dimension = view_dimension(dim, arrayed);
+                match class {
+                    naga::ImageClass::Sampled { kind, multi } => wgpu::BindingType::Texture {
+                        sample_type: match kind {
+                            naga::ScalarKind::Sint => wgpu::TextureSampleType::Sint,
+                            naga::ScalarKind::Uint => wgpu::TextureSampleType::Uint,
+                            _ => wgpu::TextureSampleType::Float { filterable: !multi },
+                        },
+                        view_dimension,
+                        multisampled: multi,
+                    },
+                    naga::ImageClass::Depth { multi } => wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Depth,
+                        view_dimension,
+                        multisampled: multi,
+                    },
+                    naga::ImageClass::Storage { format, access } => wgpu::BindingType::StorageTexture {
+                        access: match (access.contains(naga::StorageAccess::LOAD), access.contains(naga::StorageAccess::STORE)) {
+                            (true, true) => wgpu::StorageTextureAccess::ReadWrite,
+                            (true, false) => wgpu::StorageTextureAccess::ReadOnly,
+                            _ => wgpu::StorageTextureAccess::WriteOnly,
+                        },
+                        format: wgpu::wgc::validation::map_storage_format_from_naga(format),
+                        view_dimension,
+                    },
+                    naga::ImageClass::External => wgpu::BindingType::ExternalTexture,
+                }
+            }
+            naga::TypeInner::AccelerationStructure { vertex_return } => {
+                wgpu::BindingType::AccelerationStructure { vertex_return }
+            }
+            _ => return None,
+        },
+        _ => return None,
+    };
+    Some((ty, count))
+}
+
+fn view_dimension(dim: naga::ImageDimension, arrayed: bool) -> wgpu::TextureViewDimension {
+    match (dim, arrayed) {
+        (naga::ImageDimension::D1, _) => wgpu::TextureViewDimension::D1,
+        (naga::ImageDimension::D2, false) => wgpu::TextureViewDimension::D2,
+        (naga::ImageDimension::D2, true) => wgpu::TextureViewDimension::D2Array,
+        (naga::ImageDimension::D3, _) => wgpu::TextureViewDimension::D3,
+        (naga::ImageDimension::Cube, false) => wgpu::TextureViewDimension::Cube,
+        (naga::ImageDimension::Cube, true) => wgpu::TextureViewDimension::CubeArray,
+    }
+}
+
+fn vertex_input(entry_point: &str, binding: &naga::Binding, name: Option<String>, inner: &naga::TypeInner) -> Option<VertexInput> {
+    let naga::Binding::Location { location, .. } = *binding else { return None; };
+    let scalar = match *inner {
+        naga::TypeInner::Scalar(scalar) => scalar,
+        naga::TypeInner::Vector { scalar, .. } => scalar,
+        _ => return None,
+    };
+    let kind = match scalar.kind {
+        naga::ScalarKind::Sint => VertexInputKind::Sint,
+        naga::ScalarKind::Uint => VertexInputKind::Uint,
+        _ => VertexInputKind::Float,
+    };
+
+    Some(VertexInput {
+        entry_point: entry_point.to_string(),
+        location,
+        name,
+        kind,
+    })
+}
+
+fn vertex_format_kind(format: wgpu::VertexFormat) -> VertexInputKind {
+    use wgpu::VertexFormat as F;
+    match format {
+        F::Uint8 | F::Uint8x2 | F::Uint8x4 | F::Uint16 | F::Uint16x2 | F::Uint16x4 | F::Uint32 | F::Uint32x2
+        | F::Uint32x3 | F::Uint32x4 => VertexInputKind::Uint,
+        F::Sint8 | F::Sint8x2 | F::Sint8x4 | F::Sint16 | F::Sint16x2 | F::Sint16x4 | F::Sint32 | F::Sint32x2
+        | F::Sint32x3 | F::Sint32x4 => VertexInputKind::Sint,
+        _ => VertexInputKind::Float,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn errors_name_the_declaration() {
+        let vertex = ShaderReflection::from_wgsl(
+            "@group(0) @binding(0) var<uniform> scale: f32;\n\
+             @vertex fn main(@location(0) position: vec2<i32>) -> @builtin(position) vec4<f32> {\n\
+                 return vec4<f32>(vec2<f32>(position) * scale, 0.0, 1.0);\n\
+             }",
+        )
+        .unwrap();
+        let fragment = ShaderReflection::from_wgsl(
+            "@group(0) @binding(0) var<uniform> scale: vec4<f32>;\n\
+             @fragment fn main() -> @location(0) vec4<f32> { return scale; }",
+        )
+        .unwrap();
+
+        let conflict = ShaderReflection::merge_bindings([&vertex, &fragment]).unwrap_err();
+        assert!(conflict.to_string().starts_with("@group(0) @binding(0) 'scale' is declared"), "{conflict}");
+
+        let missing = vertex.check_vertex_buffers(&[]).unwrap_err();
+        assert!(missing.to_string().starts_with("Vertex input 'position' @location(0) of 'main'"), "{missing}");
+
+        let attributes = wgpu::vertex_attr_array![0 => Float32x2];
+        let buffer = wgpu::VertexBufferLayout { array_stride: 8, step_mode: wgpu::VertexStepMode::Vertex, attributes: &attributes };
+        let mismatch = vertex.check_vertex_buffers(&[buffer]).unwrap_err();
+        assert!(mismatch.to_string().contains("shader input 'position' (Sint)"), "{mismatch}");
+    }
+}
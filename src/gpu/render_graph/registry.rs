@@ -45,3 +45,13 @@ where
         }
     }
 }
+
+impl<Handle, Value> Default for InstanceRegistry<Handle, Value>
+where
+    Handle: slotmap::Key,
+    Value: Hash + Eq + Clone + Copy,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
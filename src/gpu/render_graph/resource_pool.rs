@@ -14,102 +14,1154 @@ struct ResourcePool {
 }
  */
 
+use std::collections::HashMap;
 use std::num::NonZeroU32;
 use std::sync::Arc;
+use std::sync::mpsc;
+use std::time::{Duration, Instant, SystemTime};
 
-use slotmap::SlotMap;
+use slotmap::{SecondaryMap, SlotMap};
 use wgpu::{Device, MultisampleState};
 
-use crate::gpu::render_graph::types::{ComputePipelineHandle, PipelineLayoutHandle, RenderPipelineHandle, ShaderHandle};
+use crate::gpu::render_graph::preprocessor::{PreprocessError, PreprocessedSource, Preprocessor};
+use crate::gpu::render_graph::reflection::{ReflectionError, ShaderReflection, SourceDiagnostic};
+use crate::gpu::render_graph::transient_pool::{DEFAULT_MAX_UNUSED_FRAMES, TransientPool};
+use crate::gpu::render_graph::types::{
+    ComputePipelineHandle, PipelineLayoutHandle, RenderPipelineHandle, ShaderHandle,
+};
 use std::env;
-use std::path::PathBuf;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+// `assets` next to the executable, in the working directory and in the crate sources, in the
+// order they are searched
+fn find_assets() -> Vec<PathBuf> {
+    let mut roots = Vec::new();
+    if let Ok(mut exe_path) = env::current_exe() {
+        exe_path.pop(); // Remove the executable name, leaving the directory
+        roots.push(exe_path.join("assets"));
+    }
+    if let Ok(current_dir) = env::current_dir() {
+        roots.push(current_dir.join("assets"));
+    }
+    roots.push(Path::new(env!("CARGO_MANIFEST_DIR")).join("assets"));
+
+    let mut unique = Vec::new();
+    for root in roots {
+        if !unique.contains(&root) {
+            unique.push(root);
+        }
+    }
+    unique
+}
+
+/// A shader name that matched no file; lists every path that was tried.
+#[derive(Debug)]
+pub struct ShaderNotFound {
+    pub name: String,
+    pub searched: Vec<PathBuf>,
+}
+
+impl fmt::Display for ShaderNotFound {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Shader '{}' not found, searched:", self.name)?;
+        for path in &self.searched {
+            write!(f, "\n  {}", path.display())?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ShaderNotFound {}
+
+/// Why a shader could not be loaded or reloaded.
+#[derive(Debug)]
+pub enum ShaderError {
+    NotFound(ShaderNotFound),
+    Preprocess(PreprocessError),
+    // rejected by naga; located in the original file, after include mapping
+    Compile {
+        shader: String,
+        message: String,
+        location: Option<Box<ShaderErrorLocation>>,
+    },
+    // accepted by naga, rejected by the device
+    Device { shader: String, message: String },
+}
+
+#[derive(Debug)]
+pub struct ShaderErrorLocation {
+    pub file: PathBuf,
+    pub line: u32,
+    pub column: u32,
+    // the offending line as compiled, and what the marked part is
+    pub excerpt: String,
+    pub length: u32,
+    pub label: Option<String>,
+}
+
+impl ShaderError {
+    fn compile(shader: &str, source: &PreprocessedSource, diagnostic: SourceDiagnostic) -> Self {
+        let location = diagnostic.line.and_then(|line| {
+            let (file, original_line) = source.location(line)?;
+            Some(Box::new(ShaderErrorLocation {
+                file: file.to_path_buf(),
+                line: original_line,
+                column: diagnostic.column,
+                excerpt: source.code.lines().nth(line as usize - 1).unwrap_or_default().to_string(),
+                length: diagnostic.length,
+                label: diagnostic.label,
+            }))
+        });
+
+        ShaderError::Compile {
+            shader: shader.to_string(),
+            message: diagnostic.message,
+            location,
+        }
+    }
+}
+
+impl fmt::Display for ShaderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ShaderError::NotFound(error) => write!(f, "{error}"),
+            ShaderError::Preprocess(error) => write!(f, "{error}"),
+            ShaderError::Compile { shader, message, location: None } => {
+                write!(f, "Shader '{shader}' failed to compile: {message}")
+            }
+            ShaderError::Compile { shader, message, location: Some(location) } => {
+                // rustc-like: location, the line, and a marker under the offending span
+                let number = location.line.to_string();
+                let gutter = " ".repeat(number.len());
+                let excerpt = location.excerpt.trim_end();
+                let before = (location.column as usize).saturating_sub(1);
+                let marker_start = excerpt
+                    .get(..before)
+                    .unwrap_or(excerpt)
+                    .chars()
+                    .map(|c| if c == '\t' { '\t' } else { ' ' })
+                    .collect::<String>();
+                let marker = "^".repeat(location.length.max(1) as usize);
+
+                writeln!(f, "Shader '{shader}' failed to compile: {message}")?;
+                writeln!(f, "{gutter}--> {}:{}:{}", location.file.display(), location.line, location.column)?;
+                writeln!(f, "{gutter} |")?;
+                writeln!(f, "{number} | {excerpt}")?;
+                write!(f, "{gutter} | {marker_start}{marker}")?;
+                if let Some(label) = &location.label {
+                    write!(f, " {label}")?;
+                }
+                Ok(())
+            }
+            ShaderError::Device { shader, message } => write!(f, "Shader '{shader}' was rejected by the device: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for ShaderError {}
+
+impl From<ShaderNotFound> for ShaderError {
+    fn from(error: ShaderNotFound) -> Self {
+        ShaderError::NotFound(error)
+    }
+}
+
+impl From<PreprocessError> for ShaderError {
+    fn from(error: PreprocessError) -> Self {
+        ShaderError::Preprocess(error)
+    }
+}
+
+/// Why a pipeline could not be created. Shaders are named by the name they were loaded with.
+#[derive(Debug)]
+pub enum PipelineError {
+    MissingEntryPoint { shader: String, entry_point: String, stage: wgpu::ShaderStages, available: Vec<String> },
+    WrongStage { shader: String, entry_point: String, expected: wgpu::ShaderStages, found: wgpu::ShaderStages },
+    // no entry point was named and the module has none or several for the stage
+    AmbiguousEntryPoint { shader: String, stage: wgpu::ShaderStages, available: Vec<String> },
+    VertexInput(ReflectionError),
+    // a shader variant needed by a pipeline variant failed to load
+    Shader(ShaderError),
+    MultiviewUnsupported { pipeline: Option<String> },
+    TooManyViews { pipeline: Option<String>, views: u32, supported: u32 },
+    // wgpu's validation error for a description the checks above let through
+    Creation { pipeline: Option<String>, message: String },
+}
+
+impl fmt::Display for PipelineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PipelineError::MissingEntryPoint { shader, entry_point, stage, available } => write!(
+                f,
+                "Shader '{shader}' has no {stage:?} entry point '{entry_point}', it has: {}",
+                available.join(", ")
+            ),
+            PipelineError::WrongStage { shader, entry_point, expected, found } => write!(
+                f,
+                "Entry point '{entry_point}' of shader '{shader}' is a {found:?} entry point, expected {expected:?}"
+            ),
+            PipelineError::AmbiguousEntryPoint { shader, stage, available } if available.is_empty() => {
+                write!(f, "Shader '{shader}' has no {stage:?} entry point")
+            }
+            PipelineError::AmbiguousEntryPoint { shader, stage, available } => write!(
+                f,
+                "Shader '{shader}' has several {stage:?} entry points ({}), name the one to use",
+                available.join(", ")
+            ),
+            PipelineError::VertexInput(error) => write!(f, "{error}"),
+            PipelineError::Shader(error) => write!(f, "{error}"),
+            PipelineError::MultiviewUnsupported { pipeline } => {
+                write!(f, "Pipeline {pipeline:?} uses multiview, which the device does not support")
+            }
+            PipelineError::TooManyViews { pipeline, views, supported } => {
+                write!(f, "Pipeline {pipeline:?} renders {views} views, the device supports {supported}")
+            }
+            PipelineError::Creation { pipeline, message } => write!(f, "Failed to create pipeline {pipeline:?}:\n{message}"),
+        }
+    }
+}
+
+impl std::error::Error for PipelineError {}
+
+// how often `reload_changed_shaders` looks at the shader files on disk
+const SHADER_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+// where a shader module was loaded from, to notice edits
+struct ShaderSource {
+    path: PathBuf,
+    label: String,
+    // variant keys, sorted; each is `#define`d before the source
+    defines: Vec<String>,
+    // the file and everything it includes, with the modification times they were read at
+    files: Vec<(PathBuf, Option<SystemTime>)>,
+}
+
+// owned, hashable copies of the pipeline descriptions without their labels: identical
+// requests share one pipeline, and pipelines are recreated from them when their shaders change
+// and derived into variants
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct StageKey {
+    module: ShaderHandle,
+    entry_point: Option<String>,
+    // values as bits, f64 is not hashable
+    constants: Vec<(String, u64)>,
+}
+
+impl StageKey {
+    fn new(module: ShaderHandle, entry_point: Option<&str>, constants: &[(&str, f64)]) -> Self {
+        StageKey {
+            module,
+            entry_point: entry_point.map(str::to_owned),
+            constants: constants.iter().map(|&(name, value)| (name.to_string(), value.to_bits())).collect(),
+        }
+    }
+
+    // Sets `constants` the module declares, replacing values of the same key. Modules without
+    // reflection get all of them, for wgpu to validate.
+    fn set_constants(&mut self, constants: &[(&str, f64)], reflection: Option<&ShaderReflection>) {
+        let declared = constants
+            .iter()
+            .filter(|(name, _)| reflection.is_none_or(|reflection| reflection.declares_override(name)));
+        for &(name, value) in declared {
+            match self.constants.iter_mut().find(|(existing, _)| existing == name) {
+                Some((_, existing)) => *existing = value.to_bits(),
+                None => self.constants.push((name.to_string(), value.to_bits())),
+            }
+        }
+    }
+
+    fn compilation_constants(&self) -> Vec<(&str, f64)> {
+        self.constants.iter().map(|(name, value)| (name.as_str(), f64::from_bits(*value))).collect()
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct VertexBufferKey {
+    array_stride: wgpu::BufferAddress,
+    step_mode: wgpu::VertexStepMode,
+    attributes: Vec<wgpu::VertexAttribute>,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct RenderPipelineKey {
+    layout: PipelineLayoutHandle,
+    vertex: StageKey,
+    buffers: Vec<VertexBufferKey>,
+    primitive: wgpu::PrimitiveState,
+    depth_stencil: Option<wgpu::DepthStencilState>,
+    multisample: MultisampleState,
+    fragment: Option<(StageKey, Vec<Option<wgpu::ColorTargetState>>)>,
+    multiview_mask: Option<NonZeroU32>,
+}
+
+impl RenderPipelineKey {
+    fn vertex_buffer_layouts(&self) -> Vec<wgpu::VertexBufferLayout<'_>> {
+        self.buffers.iter().map(|buffer| wgpu::VertexBufferLayout {
+            array_stride: buffer.array_stride,
+            step_mode: buffer.step_mode,
+            attributes: &buffer.attributes,
+        }).collect()
+    }
+
+    fn stages_mut(&mut self) -> impl Iterator<Item = &mut StageKey> {
+        std::iter::once(&mut self.vertex).chain(self.fragment.as_mut().map(|(stage, _)| stage))
+    }
+
+    fn uses(&self, shader: ShaderHandle) -> bool {
+        self.vertex.module == shader || self.fragment.as_ref().is_some_and(|(stage, _)| stage.module == shader)
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct ComputePipelineKey {
+    layout: PipelineLayoutHandle,
+    stage: StageKey,
+}
+
+// a pipeline compiled on a worker thread has no object until `finish_pending_pipelines`
+// installs it
+enum PipelineState<P> {
+    Ready(P),
+    Pending,
+    Failed,
+}
+
+impl<P> PipelineState<P> {
+    fn ready(&self) -> Option<&P> {
+        match self {
+            PipelineState::Ready(pipeline) => Some(pipeline),
+            _ => None,
+        }
+    }
+}
+
+// sent back by a worker thread, with wgpu's validation error when creation failed
+enum CompiledPipeline {
+    Render(RenderPipelineHandle, Result<wgpu::RenderPipeline, String>),
+    Compute(ComputePipelineHandle, Result<wgpu::ComputePipeline, String>),
+}
+
+// the wgpu objects a pipeline description refers to, cloned so the pipeline can be built on a
+// worker thread
+struct RenderPipelineParts {
+    device: wgpu::Device,
+    layout: Option<wgpu::PipelineLayout>,
+    vertex: wgpu::ShaderModule,
+    fragment: Option<wgpu::ShaderModule>,
+    cache: Option<wgpu::PipelineCache>,
+}
+
+impl RenderPipelineParts {
+    fn build(&self, desc: &RenderPipelineKey, label: Option<&str>) -> wgpu::RenderPipeline {
+        let buffers = desc.vertex_buffer_layouts();
+        let vertex_constants = desc.vertex.compilation_constants();
+        let fragment_constants = desc.fragment.as_ref().map(|(stage, _)| stage.compilation_constants());
+
+        self.device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label,
+            layout: self.layout.as_ref(),
+            vertex: wgpu::VertexState {
+                module: &self.vertex,
+                entry_point: desc.vertex.entry_point.as_deref(),
+                compilation_options: wgpu::PipelineCompilationOptions {
+                    constants: &vertex_constants,
+                    ..Default::default()
+                },
+                buffers: &buffers,
+            },
+            primitive: desc.primitive,
+            depth_stencil: desc.depth_stencil.clone(),
+            multisample: desc.multisample,
+            fragment: desc
+                .fragment
+                .as_ref()
+                .zip(self.fragment.as_ref())
+                .zip(fragment_constants.as_deref())
+                .map(|(((stage, targets), module), constants)| wgpu::FragmentState {
+                    module,
+                    entry_point: stage.entry_point.as_deref(),
+                    compilation_options: wgpu::PipelineCompilationOptions {
+                        constants,
+                        ..Default::default()
+                    },
+                    targets,
+                }),
+            multiview_mask: desc.multiview_mask,
+            cache: self.cache.as_ref(),
+        })
+    }
+}
+
+struct ComputePipelineParts {
+    device: wgpu::Device,
+    layout: Option<wgpu::PipelineLayout>,
+    module: wgpu::ShaderModule,
+    cache: Option<wgpu::PipelineCache>,
+}
+
+impl ComputePipelineParts {
+    fn build(&self, desc: &ComputePipelineKey, label: Option<&str>) -> wgpu::ComputePipeline {
+        let constants = desc.stage.compilation_constants();
+
+        self.device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label,
+            layout: self.layout.as_ref(),
+            module: &self.module,
+            entry_point: desc.stage.entry_point.as_deref(),
+            compilation_options: wgpu::PipelineCompilationOptions {
+                constants: &constants,
+                ..Default::default()
+            },
+            cache: self.cache.as_ref(),
+        })
+    }
+}
+
+// only a still pending pipeline takes the result; a shader reload may have rebuilt it meanwhile
+fn install_compiled<P>(state: Option<&mut PipelineState<P>>, result: Result<P, String>, label: Option<&str>) -> bool {
+    let Some(state) = state.filter(|state| matches!(state, PipelineState::Pending)) else { return false; };
+    match result {
+        Ok(pipeline) => {
+            *state = PipelineState::Ready(pipeline);
+            true
+        }
+        Err(error) => {
+            eprintln!("Failed to create pipeline {label:?}:\n{error}");
+            *state = PipelineState::Failed;
+            false
+        }
+    }
+}
+
+// what a pipeline variant is cached under: its base pipeline, the sorted defines and the
+// constants with their values as bits
+type VariantKey = (RenderPipelineHandle, Vec<String>, Vec<(String, u64)>);
+
+fn variant_key(base: RenderPipelineHandle, variant: &PipelineVariant) -> VariantKey {
+    let constants = variant.constants.iter().map(|&(name, value)| (name.to_string(), value.to_bits())).collect();
+    (base, sorted_keys(variant.defines), constants)
+}
+
+fn compute_pipeline_key(desc: &ComputePipelineDesc) -> ComputePipelineKey {
+    ComputePipelineKey {
+        layout: desc.layout,
+        stage: StageKey::new(desc.compute.module, desc.compute.entry_point, desc.compute.constants),
+    }
+}
 
-fn find_assets() -> PathBuf {
-    let mut exe_path = env::current_exe().expect("Failed to get exe path");
-    exe_path.pop(); // Remove the executable name, leaving the directory
-    exe_path.push("assets");
-    exe_path
+fn sorted_keys(keys: &[&str]) -> Vec<String> {
+    let mut keys: Vec<String> = keys.iter().map(|key| key.to_string()).collect();
+    keys.sort();
+    keys.dedup();
+    keys
 }
 
  pub struct Resources {
     device: Arc<wgpu::Device>,
-    assets_path: PathBuf,
+    asset_roots: Vec<PathBuf>,
 
     shaders: SlotMap<ShaderHandle, wgpu::ShaderModule>,
+    shader_sources: SecondaryMap<ShaderHandle, ShaderSource>,
+    reflections: SecondaryMap<ShaderHandle, ShaderReflection>,
+    // one module per file and set of variant keys
+    shader_variants: HashMap<(PathBuf, Vec<String>), ShaderHandle>,
+    last_shader_poll: Instant,
 
     pipelines_layouts: SlotMap<PipelineLayoutHandle, wgpu::PipelineLayout>,
 
-    render_pipelines: SlotMap<RenderPipelineHandle, wgpu::RenderPipeline>,
-    compute_pipelines: SlotMap<ComputePipelineHandle, wgpu::ComputePipeline>,
+    render_pipelines: SlotMap<RenderPipelineHandle, PipelineState<wgpu::RenderPipeline>>,
+    compute_pipelines: SlotMap<ComputePipelineHandle, PipelineState<wgpu::ComputePipeline>>,
+    render_pipeline_descs: SecondaryMap<RenderPipelineHandle, (RenderPipelineKey, Option<String>)>,
+    compute_pipeline_descs: SecondaryMap<ComputePipelineHandle, (ComputePipelineKey, Option<String>)>,
+    // the first pipeline created for each description, handed out again for identical ones
+    unique_render_pipelines: HashMap<RenderPipelineKey, RenderPipelineHandle>,
+    unique_compute_pipelines: HashMap<ComputePipelineKey, ComputePipelineHandle>,
+    render_pipeline_variants: HashMap<VariantKey, RenderPipelineHandle>,
+    // bumped whenever pipelines are rebuilt or finish compiling, so objects derived from them
+    // are recreated
+    pipeline_generation: u64,
+    // used by the graph while a pipeline is still compiling
+    render_pipeline_fallbacks: SecondaryMap<RenderPipelineHandle, RenderPipelineHandle>,
+    compute_pipeline_fallbacks: SecondaryMap<ComputePipelineHandle, ComputePipelineHandle>,
+    compiled_sender: mpsc::Sender<CompiledPipeline>,
+    compiled_receiver: mpsc::Receiver<CompiledPipeline>,
+
+    // masks of multiview render pipelines, checked against the passes using them
+    multiview_masks: SecondaryMap<RenderPipelineHandle, NonZeroU32>,
+
+    // shared by every pipeline when enabled, with the file it is persisted to
+    pipeline_cache: Option<(wgpu::PipelineCache, PathBuf)>,
+
+    // physical resources behind graph transients, recycled across frames and resizes
+    transients: TransientPool,
  }
 
  impl Resources {
     pub fn new(device: Arc<wgpu::Device>) -> Self {
+        let (compiled_sender, compiled_receiver) = mpsc::channel();
         Resources {
             device,
-            assets_path: find_assets(),
+            asset_roots: find_assets(),
 
             shaders: SlotMap::with_key(),
+            shader_sources: SecondaryMap::new(),
+            reflections: SecondaryMap::new(),
+            shader_variants: HashMap::new(),
+            last_shader_poll: Instant::now(),
 
             pipelines_layouts: SlotMap::with_key(),
 
             render_pipelines: SlotMap::with_key(),
             compute_pipelines: SlotMap::with_key(),
+            render_pipeline_descs: SecondaryMap::new(),
+            compute_pipeline_descs: SecondaryMap::new(),
+            unique_render_pipelines: HashMap::new(),
+            unique_compute_pipelines: HashMap::new(),
+            render_pipeline_variants: HashMap::new(),
+            pipeline_generation: 0,
+            render_pipeline_fallbacks: SecondaryMap::new(),
+            compute_pipeline_fallbacks: SecondaryMap::new(),
+            compiled_sender,
+            compiled_receiver,
+
+            multiview_masks: SecondaryMap::new(),
+
+            pipeline_cache: None,
+
+            transients: TransientPool::new(DEFAULT_MAX_UNUSED_FRAMES),
+        }
+    }
+    /// Path of the shader `name` under `assets/shaders/`, e.g. `"base"` or `"post/blur"` for
+    /// `post/blur.wgsl`. The asset roots are searched in order and the first match wins.
+    pub fn resolve_shader(&self, name: &str) -> Result<PathBuf, ShaderNotFound> {
+        let file = if name.ends_with(".wgsl") { name.to_string() } else { format!("{name}.wgsl") };
+        let searched: Vec<PathBuf> = self.asset_roots.iter().map(|root| root.join("shaders").join(&file)).collect();
+
+        match searched.iter().find(|path| path.is_file()) {
+            Some(path) => Ok(path.clone()),
+            None => Err(ShaderNotFound { name: name.to_string(), searched }),
         }
     }
-    pub fn load_shader(&mut self, name: &str) -> ShaderHandle {
-        let mut path = self.assets_path.clone();
-        path.push("shaders");
-        path.set_extension("wgsl");
+    /// Loads the module `name` (see `resolve_shader`).
+    pub fn load_shader(&mut self, name: &str) -> Result<ShaderHandle, ShaderError> {
+        self.load_shader_variant(name, &[])
+    }
+    /// Loads the module `name` with every key in `keys` defined, e.g. `NORMAL_MAP` or
+    /// `SKINNED` for `#ifdef` blocks. Each distinct set of keys is compiled once; the order of
+    /// `keys` does not matter.
+    pub fn load_shader_variant(&mut self, name: &str, keys: &[&str]) -> Result<ShaderHandle, ShaderError> {
+        let path = self.resolve_shader(name)?;
+        self.load_shader_file(name, path, sorted_keys(keys))
+    }
+    /// `shader` with `keys` defined on top of the keys it was loaded with.
+    pub fn shader_variant(&mut self, shader: ShaderHandle, keys: &[&str]) -> Result<ShaderHandle, ShaderError> {
+        if keys.is_empty() {
+            return Ok(shader);
+        }
+        let source = &self.shader_sources[shader];
+        let mut defines = source.defines.clone();
+        defines.extend(keys.iter().map(|key| key.to_string()));
+        defines.sort();
+        defines.dedup();
 
-        let source_code = std::fs::read_to_string(&path)
-            .unwrap_or_else(|_| panic!("Failed to find shader file at {:?}", path));
+        let (name, path) = (source.label.clone(), source.path.clone());
+        self.load_shader_file(&name, path, defines)
+    }
+    /// Loads the vertex and fragment stages of `name`: either one module `name.wgsl` with both
+    /// entry points, or the pair `name_vertex.wgsl` and `name_fragment.wgsl`.
+    pub fn load_shader_stages(&mut self, name: &str) -> Result<ShaderStages, ShaderError> {
+        let single = match self.resolve_shader(name) {
+            Ok(path) => {
+                let module = self.load_shader_file(name, path, Vec::new())?;
+                return Ok(ShaderStages { vertex: module, fragment: module });
+            }
+            Err(error) => error,
+        };
 
-        self.shaders.insert(self.device.create_shader_module(
-            wgpu::ShaderModuleDescriptor {
-                label: Some(name),
-                source: wgpu::ShaderSource::Wgsl(source_code.into())
+        let vertex_name = format!("{name}_vertex");
+        let fragment_name = format!("{name}_fragment");
+        match (self.resolve_shader(&vertex_name), self.resolve_shader(&fragment_name)) {
+            (Ok(vertex), Ok(fragment)) => Ok(ShaderStages {
+                vertex: self.load_shader_file(&vertex_name, vertex, Vec::new())?,
+                fragment: self.load_shader_file(&fragment_name, fragment, Vec::new())?,
+            }),
+            (vertex, fragment) => {
+                let mut searched = single.searched;
+                searched.extend(vertex.err().into_iter().chain(fragment.err()).flat_map(|error| error.searched));
+                Err(ShaderNotFound { name: name.to_string(), searched }.into())
             }
-        ))
+        }
+    }
+    fn load_shader_file(&mut self, name: &str, path: PathBuf, defines: Vec<String>) -> Result<ShaderHandle, ShaderError> {
+        let key = (path, defines);
+        if let Some(&handle) = self.shader_variants.get(&key) {
+            return Ok(handle);
+        }
+        let (path, defines) = key;
+
+        let (module, reflection, source) = self.compile_shader(name, &path, &defines)?;
+
+        let handle = self.shaders.insert(module);
+        self.reflections.insert(handle, reflection);
+        self.shader_variants.insert((path.clone(), defines.clone()), handle);
+        self.shader_sources.insert(handle, ShaderSource {
+            path,
+            label: name.to_string(),
+            defines,
+            files: watched_files(&source),
+        });
+        Ok(handle)
+    }
+    // preprocesses, validates with naga for readable errors, then creates the module; anything
+    // only the device rejects is still caught in an error scope
+    fn compile_shader(
+        &self,
+        name: &str,
+        path: &Path,
+        defines: &[String],
+    ) -> Result<(wgpu::ShaderModule, ShaderReflection, PreprocessedSource), ShaderError> {
+        let source = self.preprocess(path, defines)?;
+        let reflection = match ShaderReflection::from_wgsl(&source.code) {
+            Ok(reflection) => reflection,
+            Err(ReflectionError::Parse(diagnostic) | ReflectionError::Validation(diagnostic)) => {
+                return Err(ShaderError::compile(name, &source, diagnostic));
+            }
+            Err(error) => return Err(ShaderError::compile(name, &source, SourceDiagnostic {
+                message: error.to_string(),
+                label: None,
+                line: None,
+                column: 0,
+                length: 0,
+            })),
+        };
+
+        let label = if defines.is_empty() { name.to_string() } else { format!("{name}[{}]", defines.join(",")) };
+        let scope = self.device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let module = self.device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some(&label),
+            source: wgpu::ShaderSource::Wgsl(source.code.as_str().into()),
+        });
+        if let Some(error) = pollster::block_on(scope.pop()) {
+            return Err(ShaderError::Device {
+                shader: label,
+                message: source.map_locations(&error.to_string()),
+            });
+        }
+        Ok((module, reflection, source))
+    }
+    // includes resolve next to the including file, then under every `assets/shaders`
+    fn preprocess(&self, path: &Path, defines: &[String]) -> Result<PreprocessedSource, PreprocessError> {
+        let include_roots: Vec<PathBuf> = self.asset_roots.iter().map(|root| root.join("shaders")).collect();
+        defines
+            .iter()
+            .fold(Preprocessor::new(&include_roots), |preprocessor, define| preprocessor.define(define, ""))
+            .run(path)
+    }
+    /// Recompiles shaders whose file changed on disk and rebuilds the pipelines created from
+    /// them, under the same handles. A shader or pipeline failing validation keeps its previous
+    /// version. Checks the files at most every `SHADER_POLL_INTERVAL`; returns the number of
+    /// shaders reloaded.
+    pub fn reload_changed_shaders(&mut self) -> usize {
+        if self.last_shader_poll.elapsed() < SHADER_POLL_INTERVAL {
+            return 0;
+        }
+        self.last_shader_poll = Instant::now();
+
+        let changed: Vec<ShaderHandle> = self
+            .shader_sources
+            .iter_mut()
+            .filter_map(|(handle, source)| {
+                let mut changed = false;
+                for (path, modified) in &mut source.files {
+                    let current = modified_time(path);
+                    if current.is_some() && current != *modified {
+                        *modified = current;
+                        changed = true;
+                    }
+                }
+                changed.then_some(handle)
+            })
+            .collect();
+
+        changed.into_iter().filter(|&shader| self.reload_shader(shader)).count()
+    }
+    fn reload_shader(&mut self, shader: ShaderHandle) -> bool {
+        let ShaderSource { path, label, defines, .. } = &self.shader_sources[shader];
+        let (module, reflection, source) = match self.compile_shader(label, path, defines) {
+            Ok(compiled) => compiled,
+            Err(error) => {
+                eprintln!("{error}\nKeeping the previous version of {path:?}");
+                return false;
+            }
+        };
+        self.shaders[shader] = module;
+        self.reflections.insert(shader, reflection);
+        // an edit may have added or removed includes
+        self.shader_sources[shader].files = watched_files(&source);
+
+        let render: Vec<RenderPipelineHandle> = self
+            .render_pipeline_descs
+            .iter()
+            .filter(|(_, (key, _))| key.uses(shader))
+            .map(|(handle, _)| handle)
+            .collect();
+        for handle in render {
+            let scope = self.device.push_error_scope(wgpu::ErrorFilter::Validation);
+            let (key, label) = &self.render_pipeline_descs[handle];
+            let pipeline = self.build_render_pipeline(key, label.as_deref());
+            match pollster::block_on(scope.pop()) {
+                None => self.render_pipelines[handle] = PipelineState::Ready(pipeline),
+                Some(error) => eprintln!("Failed to rebuild pipeline {label:?}, keeping the previous version:\n{error}"),
+            }
+        }
+
+        let compute: Vec<ComputePipelineHandle> = self
+            .compute_pipeline_descs
+            .iter()
+            .filter(|(_, (key, _))| key.stage.module == shader)
+            .map(|(handle, _)| handle)
+            .collect();
+        for handle in compute {
+            let scope = self.device.push_error_scope(wgpu::ErrorFilter::Validation);
+            let (key, label) = &self.compute_pipeline_descs[handle];
+            let pipeline = self.build_compute_pipeline(key, label.as_deref());
+            match pollster::block_on(scope.pop()) {
+                None => self.compute_pipelines[handle] = PipelineState::Ready(pipeline),
+                Some(error) => eprintln!("Failed to rebuild pipeline {label:?}, keeping the previous version:\n{error}"),
+            }
+        }
+
+        self.pipeline_generation += 1;
+        true
+    }
+    /// Changes every time pipelines are rebuilt by `reload_changed_shaders` or installed by
+    /// `finish_pending_pipelines`.
+    pub fn pipeline_generation(&self) -> u64 {
+        self.pipeline_generation
     }
     pub fn add_pipeline_layout(&mut self, desc: wgpu::PipelineLayoutDescriptor) -> PipelineLayoutHandle {
         self.pipelines_layouts.insert(
             self.device.create_pipeline_layout(&desc)
         )
     }
-    pub fn create_render_pipeline(&mut self, desc: RenderPipelineDesc) -> RenderPipelineHandle {
-        let descriptor = wgpu::RenderPipelineDescriptor {
-            label: desc.label,
-            layout: self.pipelines_layouts.get(desc.layout),
-            vertex: wgpu::VertexState {
-                module: self.shaders.get(desc.vertex.module).unwrap(),
-                entry_point: None,
-                compilation_options: wgpu::PipelineCompilationOptions::default(),
-                buffers: desc.vertex.buffers,
-            },
+    /// Creates the bind group layouts and the pipeline layout the `@group/@binding`
+    /// declarations of `shaders` describe, e.g. the vertex and fragment module of a pipeline.
+    /// Groups no shader uses get an empty layout.
+    pub fn create_reflected_layout(
+        &mut self,
+        label: Option<&str>,
+        shaders: &[ShaderHandle],
+    ) -> Result<PipelineLayoutHandle, ReflectionError> {
+        let groups = ShaderReflection::merge_bindings(shaders.iter().filter_map(|&shader| self.reflections.get(shader)))?;
+        let group_count = groups.keys().next_back().map_or(0, |&last| last + 1);
+
+        let bind_group_layouts: Vec<wgpu::BindGroupLayout> = (0..group_count)
+            .map(|group| {
+                let entries: Vec<wgpu::BindGroupLayoutEntry> = groups
+                    .get(&group)
+                    .into_iter()
+                    .flatten()
+                    .map(|binding| wgpu::BindGroupLayoutEntry {
+                        binding: binding.binding,
+                        visibility: binding.visibility,
+                        ty: binding.ty,
+                        count: binding.count,
+                    })
+                    .collect();
+                self.device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                    label: label.map(|label| format!("{label}/Group {group}")).as_deref(),
+                    entries: &entries,
+                })
+            })
+            .collect();
+
+        let layout = self.device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label,
+            bind_group_layouts: &bind_group_layouts.iter().collect::<Vec<_>>(),
+            immediate_size: 0,
+        });
+        Ok(self.pipelines_layouts.insert(layout))
+    }
+    /// Creates a render pipeline after checking its entry points and vertex buffers against
+    /// the reflected shaders. A description identical to an earlier one, apart from the label,
+    /// returns the earlier pipeline.
+    pub fn create_render_pipeline(&mut self, desc: RenderPipelineDesc) -> Result<RenderPipelineHandle, PipelineError> {
+        let key = self.render_pipeline_key(&desc)?;
+        self.insert_render_pipeline(key, desc.label, false)
+    }
+    /// Like `create_render_pipeline`, but the pipeline is compiled on a worker thread instead
+    /// of stalling the caller. The handle stays pending until `finish_pending_pipelines` picks
+    /// the pipeline up; meanwhile the graph binds `fallback` for passes using it, which should
+    /// share its bind group layout, or skips their closures without one. An identical
+    /// description returns the earlier handle while it is pending or ready.
+    pub fn create_render_pipeline_async(
+        &mut self,
+        desc: RenderPipelineDesc,
+        fallback: Option<RenderPipelineHandle>,
+    ) -> Result<RenderPipelineHandle, PipelineError> {
+        let key = self.render_pipeline_key(&desc)?;
+        let handle = self.insert_render_pipeline(key, desc.label, true)?;
+        if let Some(fallback) = fallback {
+            self.render_pipeline_fallbacks.insert(handle, fallback);
+        }
+        Ok(handle)
+    }
+    /// Whether the device renders `views` views in one pass, e.g. before loading shaders using
+    /// `@builtin(view_index)`, which a device without multiview rejects.
+    pub fn check_multiview(&self, views: u32, pipeline: Option<&str>) -> Result<(), PipelineError> {
+        let pipeline = pipeline.map(str::to_owned);
+        if !self.device.features().contains(wgpu::Features::MULTIVIEW) {
+            return Err(PipelineError::MultiviewUnsupported { pipeline });
+        }
+        let supported = self.device.limits().max_multiview_view_count;
+        if views > supported {
+            return Err(PipelineError::TooManyViews { pipeline, views, supported });
+        }
+        Ok(())
+    }
+    fn render_pipeline_key(&self, desc: &RenderPipelineDesc) -> Result<RenderPipelineKey, PipelineError> {
+        if let Some(mask) = desc.multiview_mask {
+            self.check_multiview(u32::BITS - mask.leading_zeros(), desc.label)?;
+        }
+
+        let key = RenderPipelineKey {
+            layout: desc.layout,
+            vertex: StageKey::new(desc.vertex.module, desc.vertex.entry_point, desc.vertex.constants),
+            buffers: desc.vertex.buffers.iter().map(|buffer| VertexBufferKey {
+                array_stride: buffer.array_stride,
+                step_mode: buffer.step_mode,
+                attributes: buffer.attributes.to_vec(),
+            }).collect(),
             primitive: desc.primitive,
-            depth_stencil: desc.depth_stencil,
+            depth_stencil: desc.depth_stencil.clone(),
             multisample: desc.multisample,
-            fragment: if let Some(fragment) = desc.fragment {
-                Some(wgpu::FragmentState {
-                    module: self.shaders.get(fragment.module).unwrap(),
-                    entry_point: None,
-                    compilation_options: wgpu::PipelineCompilationOptions::default(),
-                    targets: fragment.targets,
-                })
-            } else {None},
+            fragment: desc.fragment.as_ref().map(|fragment| {
+                let stage = StageKey::new(fragment.module, fragment.entry_point, fragment.constants);
+                (stage, fragment.targets.to_vec())
+            }),
             multiview_mask: desc.multiview_mask,
-            cache: None,
         };
+        Ok(key)
+    }
+    pub fn create_compute_pipeline(&mut self, desc: ComputePipelineDesc) -> Result<ComputePipelineHandle, PipelineError> {
+        let key = compute_pipeline_key(&desc);
+        self.insert_compute_pipeline(key, desc.label, false)
+    }
+    /// Compute counterpart of `create_render_pipeline_async`.
+    pub fn create_compute_pipeline_async(
+        &mut self,
+        desc: ComputePipelineDesc,
+        fallback: Option<ComputePipelineHandle>,
+    ) -> Result<ComputePipelineHandle, PipelineError> {
+        let key = compute_pipeline_key(&desc);
+        let handle = self.insert_compute_pipeline(key, desc.label, true)?;
+        if let Some(fallback) = fallback {
+            self.compute_pipeline_fallbacks.insert(handle, fallback);
+        }
+        Ok(handle)
+    }
+    /// Installs the pipelines worker threads finished compiling since the last call. A pipeline
+    /// failing validation stays unavailable and is no longer returned for its description, so
+    /// creating it again compiles it anew. Returns the number of pipelines installed.
+    pub fn finish_pending_pipelines(&mut self) -> usize {
+        let mut installed = 0;
+        while let Ok(compiled) = self.compiled_receiver.try_recv() {
+            let ready = match compiled {
+                CompiledPipeline::Render(handle, result) => {
+                    let label = self.render_pipeline_descs.get(handle).and_then(|(_, label)| label.as_deref());
+                    let ready = install_compiled(self.render_pipelines.get_mut(handle), result, label);
+                    if let Some(PipelineState::Failed) = self.render_pipelines.get(handle) {
+                        let (key, _) = &self.render_pipeline_descs[handle];
+                        self.unique_render_pipelines.remove(key);
+                    }
+                    ready
+                }
+                CompiledPipeline::Compute(handle, result) => {
+                    let label = self.compute_pipeline_descs.get(handle).and_then(|(_, label)| label.as_deref());
+                    let ready = install_compiled(self.compute_pipelines.get_mut(handle), result, label);
+                    if let Some(PipelineState::Failed) = self.compute_pipelines.get(handle) {
+                        let (key, _) = &self.compute_pipeline_descs[handle];
+                        self.unique_compute_pipelines.remove(key);
+                    }
+                    ready
+                }
+            };
+            installed += ready as usize;
+        }
 
-        self.render_pipelines.insert(
-            self.device.create_render_pipeline(&descriptor)
-        )
+        if installed > 0 {
+            self.pipeline_generation += 1;
+        }
+        installed
     }
-    pub fn create_compute_pipeline(&mut self, desc: wgpu::ComputePipelineDescriptor) -> ComputePipelineHandle {
-        self.compute_pipelines.insert(
-            self.device.create_compute_pipeline(&desc)
-        )
+    /// `base` with its shaders swapped for their `variant.defines` variants and
+    /// `variant.constants` set on every stage whose module declares the override, replacing
+    /// the base pipeline's value for the same key. Each distinct variant of a pipeline is
+    /// created once.
+    pub fn render_pipeline_variant(
+        &mut self,
+        base: RenderPipelineHandle,
+        variant: &PipelineVariant,
+    ) -> Result<RenderPipelineHandle, PipelineError> {
+        let variant_key = variant_key(base, variant);
+        if let Some(&handle) = self.render_pipeline_variants.get(&variant_key) {
+            return Ok(handle);
+        }
+
+        let (mut key, label) = self.render_pipeline_descs[base].clone();
+        for stage in key.stages_mut() {
+            stage.module = self.shader_variant(stage.module, variant.defines).map_err(PipelineError::Shader)?;
+            stage.set_constants(variant.constants, self.reflections.get(stage.module));
+        }
+
+        let handle = self.insert_render_pipeline(key, label.as_deref(), false)?;
+        self.render_pipeline_variants.insert(variant_key, handle);
+        Ok(handle)
+    }
+    // `background` compiles the pipeline on a worker thread, leaving it pending
+    fn insert_render_pipeline(
+        &mut self,
+        key: RenderPipelineKey,
+        label: Option<&str>,
+        background: bool,
+    ) -> Result<RenderPipelineHandle, PipelineError> {
+        if let Some(&handle) = self.unique_render_pipelines.get(&key) {
+            return Ok(handle);
+        }
+
+        self.check_stage(&key.vertex, wgpu::ShaderStages::VERTEX)?;
+        if let Some((fragment, _)) = &key.fragment {
+            self.check_stage(fragment, wgpu::ShaderStages::FRAGMENT)?;
+        }
+        if let Some(reflection) = self.reflections.get(key.vertex.module) {
+            let entry_point = self.entry_point_name(&key.vertex, wgpu::ShaderStages::VERTEX);
+            let inputs = ShaderReflection {
+                vertex_inputs: reflection
+                    .vertex_inputs
+                    .iter()
+                    .filter(|input| Some(&input.entry_point) == entry_point.as_ref())
+                    .cloned()
+                    .collect(),
+                ..Default::default()
+            };
+            inputs.check_vertex_buffers(&key.vertex_buffer_layouts()).map_err(PipelineError::VertexInput)?;
+        }
+
+        let handle = if background {
+            let handle = self.render_pipelines.insert(PipelineState::Pending);
+            self.spawn_render_pipeline(handle, &key, label);
+            handle
+        } else {
+            let pipeline = self.validated(label, || self.build_render_pipeline(&key, label))?;
+            self.render_pipelines.insert(PipelineState::Ready(pipeline))
+        };
+        if let Some(mask) = key.multiview_mask {
+            self.multiview_masks.insert(handle, mask);
+        }
+        self.unique_render_pipelines.insert(key.clone(), handle);
+        self.render_pipeline_descs.insert(handle, (key, label.map(str::to_owned)));
+        Ok(handle)
+    }
+    fn insert_compute_pipeline(
+        &mut self,
+        key: ComputePipelineKey,
+        label: Option<&str>,
+        background: bool,
+    ) -> Result<ComputePipelineHandle, PipelineError> {
+        if let Some(&handle) = self.unique_compute_pipelines.get(&key) {
+            return Ok(handle);
+        }
+        self.check_stage(&key.stage, wgpu::ShaderStages::COMPUTE)?;
+
+        let handle = if background {
+            let handle = self.compute_pipelines.insert(PipelineState::Pending);
+            self.spawn_compute_pipeline(handle, &key, label);
+            handle
+        } else {
+            let pipeline = self.validated(label, || self.build_compute_pipeline(&key, label))?;
+            self.compute_pipelines.insert(PipelineState::Ready(pipeline))
+        };
+        self.unique_compute_pipelines.insert(key.clone(), handle);
+        self.compute_pipeline_descs.insert(handle, (key, label.map(str::to_owned)));
+        Ok(handle)
+    }
+    // creates a pipeline inside an error scope, so a description wgpu rejects fails here
+    // instead of leaving an invalid pipeline behind that is deduplicated and used later
+    fn validated<P>(&self, label: Option<&str>, build: impl FnOnce() -> P) -> Result<P, PipelineError> {
+        let scope = self.device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let pipeline = build();
+        match pollster::block_on(scope.pop()) {
+            None => Ok(pipeline),
+            Some(error) => Err(PipelineError::Creation { pipeline: label.map(str::to_owned), message: error.to_string() }),
+        }
+    }
+    // the named entry point has to exist for `expected`; without a name the module needs
+    // exactly one. Shaders without reflection are left to wgpu's validation.
+    fn check_stage(&self, stage: &StageKey, expected: wgpu::ShaderStages) -> Result<(), PipelineError> {
+        let Some(reflection) = self.reflections.get(stage.module) else { return Ok(()); };
+        let shader = self.shader_sources[stage.module].label.clone();
+        let available: Vec<String> = reflection
+            .entry_points
+            .iter()
+            .filter(|entry_point| entry_point.stage == expected)
+            .map(|entry_point| entry_point.name.clone())
+            .collect();
+
+        match &stage.entry_point {
+            Some(name) => match reflection.entry_points.iter().find(|entry_point| entry_point.name == *name) {
+                Some(entry_point) if entry_point.stage == expected => Ok(()),
+                Some(entry_point) => Err(PipelineError::WrongStage {
+                    shader,
+                    entry_point: name.clone(),
+                    expected,
+                    found: entry_point.stage,
+                }),
+                None => Err(PipelineError::MissingEntryPoint {
+                    shader,
+                    entry_point: name.clone(),
+                    stage: expected,
+                    available,
+                }),
+            },
+            None if available.len() == 1 => Ok(()),
+            None => Err(PipelineError::AmbiguousEntryPoint { shader, stage: expected, available }),
+        }
+    }
+    // the entry point `stage` runs: the named one, or the only one of its stage
+    fn entry_point_name(&self, stage: &StageKey, expected: wgpu::ShaderStages) -> Option<String> {
+        stage.entry_point.clone().or_else(|| {
+            self.reflections
+                .get(stage.module)?
+                .entry_points
+                .iter()
+                .find(|entry_point| entry_point.stage == expected)
+                .map(|entry_point| entry_point.name.clone())
+        })
+    }
+    fn render_pipeline_parts(&self, desc: &RenderPipelineKey) -> RenderPipelineParts {
+        RenderPipelineParts {
+            device: (*self.device).clone(),
+            layout: self.pipelines_layouts.get(desc.layout).cloned(),
+            vertex: self.shaders[desc.vertex.module].clone(),
+            fragment: desc.fragment.as_ref().map(|(stage, _)| self.shaders[stage.module].clone()),
+            cache: self.pipeline_cache.as_ref().map(|(cache, _)| cache.clone()),
+        }
+    }
+    fn compute_pipeline_parts(&self, desc: &ComputePipelineKey) -> ComputePipelineParts {
+        ComputePipelineParts {
+            device: (*self.device).clone(),
+            layout: self.pipelines_layouts.get(desc.layout).cloned(),
+            module: self.shaders[desc.stage.module].clone(),
+            cache: self.pipeline_cache.as_ref().map(|(cache, _)| cache.clone()),
+        }
+    }
+    fn build_render_pipeline(&self, desc: &RenderPipelineKey, label: Option<&str>) -> wgpu::RenderPipeline {
+        self.render_pipeline_parts(desc).build(desc, label)
+    }
+    fn build_compute_pipeline(&self, desc: &ComputePipelineKey, label: Option<&str>) -> wgpu::ComputePipeline {
+        self.compute_pipeline_parts(desc).build(desc, label)
+    }
+    // error scopes are per thread, so the worker catches its own validation errors
+    fn spawn_render_pipeline(&self, handle: RenderPipelineHandle, desc: &RenderPipelineKey, label: Option<&str>) {
+        let parts = self.render_pipeline_parts(desc);
+        let desc = desc.clone();
+        let label = label.map(str::to_owned);
+        let sender = self.compiled_sender.clone();
+
+        std::thread::spawn(move || {
+            let scope = parts.device.push_error_scope(wgpu::ErrorFilter::Validation);
+            let pipeline = parts.build(&desc, label.as_deref());
+            let result = match pollster::block_on(scope.pop()) {
+                None => Ok(pipeline),
+                Some(error) => Err(error.to_string()),
+            };
+            // fails only when the resources were dropped meanwhile
+            let _ = sender.send(CompiledPipeline::Render(handle, result));
+        });
+    }
+    fn spawn_compute_pipeline(&self, handle: ComputePipelineHandle, desc: &ComputePipelineKey, label: Option<&str>) {
+        let parts = self.compute_pipeline_parts(desc);
+        let desc = desc.clone();
+        let label = label.map(str::to_owned);
+        let sender = self.compiled_sender.clone();
+
+        std::thread::spawn(move || {
+            let scope = parts.device.push_error_scope(wgpu::ErrorFilter::Validation);
+            let pipeline = parts.build(&desc, label.as_deref());
+            let result = match pollster::block_on(scope.pop()) {
+                None => Ok(pipeline),
+                Some(error) => Err(error.to_string()),
+            };
+            let _ = sender.send(CompiledPipeline::Compute(handle, result));
+        });
+    }
+    /// Creates a pipeline cache shared by all pipelines created afterwards, seeded from a file
+    /// in `directory` written by `save_pipeline_cache` on a previous run. The file is named after
+    /// the adapter and driver, so other GPUs and driver updates start over. Returns false when
+    /// the device or backend has no pipeline cache.
+    pub fn enable_pipeline_cache(&mut self, adapter_info: &wgpu::AdapterInfo, directory: &Path) -> bool {
+        if !self.device.features().contains(wgpu::Features::PIPELINE_CACHE) {
+            return false;
+        }
+        let Some(key) = wgpu::util::pipeline_cache_key(adapter_info) else { return false; };
+
+        let driver: String = format!("{}_{}", adapter_info.driver, adapter_info.driver_info)
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect();
+        let path = directory.join(format!("{key}_{driver}"));
+        let data = std::fs::read(&path).ok();
+
+        // SAFETY: the data was written by `save_pipeline_cache` for this adapter and driver;
+        // with `fallback` wgpu starts an empty cache if the driver still rejects it
+        let cache = unsafe {
+            self.device.create_pipeline_cache(&wgpu::PipelineCacheDescriptor {
+                label: Some("Pipeline cache"),
+                data: data.as_deref(),
+                fallback: true,
+            })
+        };
+        self.pipeline_cache = Some((cache, path));
+        true
+    }
+    /// Writes the pipeline cache back to its file, if one is enabled.
+    pub fn save_pipeline_cache(&self) -> std::io::Result<()> {
+        let Some((cache, path)) = &self.pipeline_cache else { return Ok(()); };
+        let Some(data) = cache.get_data() else { return Ok(()); };
+
+        if let Some(directory) = path.parent() {
+            std::fs::create_dir_all(directory)?;
+        }
+        // a crash mid-write must not leave a truncated cache behind
+        let temporary = path.with_extension("tmp");
+        std::fs::write(&temporary, data)?;
+        std::fs::rename(&temporary, path)
+    }
+    /// None while the pipeline is still compiling or when it failed to.
+    pub fn get_render_pipeline(&self, handle: RenderPipelineHandle) -> Option<&wgpu::RenderPipeline> {
+        self.render_pipelines.get(handle)?.ready()
+    }
+    pub fn get_compute_pipeline(&self, handle: ComputePipelineHandle) -> Option<&wgpu::ComputePipeline> {
+        self.compute_pipelines.get(handle)?.ready()
+    }
+    /// The pipeline, or its fallback while it is not ready.
+    pub fn render_pipeline_or_fallback(&self, handle: RenderPipelineHandle) -> Option<&wgpu::RenderPipeline> {
+        self.get_render_pipeline(handle)
+            .or_else(|| self.get_render_pipeline(*self.render_pipeline_fallbacks.get(handle)?))
+    }
+    pub fn compute_pipeline_or_fallback(&self, handle: ComputePipelineHandle) -> Option<&wgpu::ComputePipeline> {
+        self.get_compute_pipeline(handle)
+            .or_else(|| self.get_compute_pipeline(*self.compute_pipeline_fallbacks.get(handle)?))
+    }
+    pub fn multiview_mask(&self, handle: RenderPipelineHandle) -> Option<NonZeroU32> {
+        self.multiview_masks.get(handle).copied()
+    }
+    pub fn transient_pool(&self) -> &TransientPool {
+        &self.transients
+    }
+    pub fn transient_pool_mut(&mut self) -> &mut TransientPool {
+        &mut self.transients
     }
  }
 
@@ -126,10 +1178,117 @@ fn find_assets() -> PathBuf {
 
  pub struct VertexState<'a> {
     pub module: ShaderHandle,
+    // may be None when the module has a single vertex entry point
+    pub entry_point: Option<&'a str>,
     pub buffers: &'a [wgpu::VertexBufferLayout<'a>],
+    // values of WGSL `override` declarations, by name or `@id`
+    pub constants: &'a [(&'a str, f64)],
  }
 
  pub struct FragmentState<'a> {
     pub module: ShaderHandle,
+    pub entry_point: Option<&'a str>,
     pub targets: &'a [Option<wgpu::ColorTargetState>],
- }
\ No newline at end of file
+    pub constants: &'a [(&'a str, f64)],
+ }
+
+ pub struct ComputePipelineDesc<'a> {
+    pub label: Option<&'a str>,
+    pub layout: PipelineLayoutHandle,
+    pub compute: ComputeState<'a>,
+ }
+
+ pub struct ComputeState<'a> {
+    pub module: ShaderHandle,
+    pub entry_point: Option<&'a str>,
+    pub constants: &'a [(&'a str, f64)],
+ }
+
+/// Keys and override constants a pipeline variant differs from its base pipeline by.
+#[derive(Clone, Copy, Default)]
+pub struct PipelineVariant<'a> {
+    // defined in every shader of the pipeline
+    pub defines: &'a [&'a str],
+    // set on the stages whose module declares the override, replacing the base pipeline's
+    // value for the same key
+    pub constants: &'a [(&'a str, f64)],
+}
+
+/// Modules holding the stages of a render pipeline; the same module when it has both.
+#[derive(Clone, Copy, Debug)]
+pub struct ShaderStages {
+    pub vertex: ShaderHandle,
+    pub fragment: ShaderHandle,
+}
+
+fn watched_files(source: &PreprocessedSource) -> Vec<(PathBuf, Option<SystemTime>)> {
+    source.files().iter().map(|path| (path.clone(), modified_time(path))).collect()
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gpu::context::test_device;
+
+    #[test]
+    fn variant_constants_replace_by_key_on_declaring_stages() {
+        let reflection = ShaderReflection::from_wgsl(
+            "override SCALE: f32 = 1.0;\n@id(7) override BIAS: f32 = 0.0;\n\
+             @fragment fn main() -> @location(0) vec4<f32> { return vec4<f32>(SCALE + BIAS); }",
+        )
+        .unwrap();
+        let mut stage = StageKey::new(ShaderHandle::default(), None, &[("SCALE", 2.0)]);
+
+        stage.set_constants(&[("SCALE", 0.5), ("7", 1.0), ("BRIGHTNESS", 3.0)], Some(&reflection));
+        assert_eq!(stage.compilation_constants(), [("SCALE", 0.5), ("7", 1.0)]);
+
+        // a module declaring none of them keeps its constants
+        let mut other = StageKey::new(ShaderHandle::default(), None, &[]);
+        other.set_constants(&[("SCALE", 0.5)], Some(&ShaderReflection::default()));
+        assert!(other.constants.is_empty());
+    }
+
+    #[test]
+    #[ignore = "needs a GPU adapter"]
+    fn deduplicates_only_pipelines_that_were_created() {
+        let (device, _queue) = test_device();
+        let mut resources = Resources::new(Arc::new(device));
+        let shader = resources.load_shader_stages("base").unwrap();
+        let layout = resources.create_reflected_layout(None, &[shader.vertex, shader.fragment]).unwrap();
+
+        let desc = |targets| RenderPipelineDesc {
+            label: Some("Triangle"),
+            layout,
+            vertex: VertexState { module: shader.vertex, entry_point: None, buffers: &[], constants: &[] },
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            fragment: Some(FragmentState { module: shader.fragment, entry_point: None, targets, constants: &[] }),
+            multiview_mask: None,
+        };
+        let uint = [Some(wgpu::TextureFormat::Rgba8Uint.into())];
+        let unorm = [Some(wgpu::TextureFormat::Rgba8Unorm.into())];
+
+        // the shader writes floats, which an integer target cannot take
+        for _ in 0..2 {
+            assert!(matches!(resources.create_render_pipeline(desc(&uint)), Err(PipelineError::Creation { .. })));
+        }
+        let pipeline = resources.create_render_pipeline(desc(&unorm)).unwrap();
+        assert_eq!(resources.create_render_pipeline(desc(&unorm)).unwrap(), pipeline);
+
+        // compiled in the background, the failure shows up only once the worker is done
+        let failed = resources.create_render_pipeline_async(desc(&uint), None).unwrap();
+        assert_eq!(resources.create_render_pipeline_async(desc(&uint), None).unwrap(), failed);
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(10);
+        while matches!(resources.render_pipelines[failed], PipelineState::Pending) {
+            assert!(std::time::Instant::now() < deadline, "the worker never finished the pipeline");
+            resources.finish_pending_pipelines();
+            std::thread::yield_now();
+        }
+        assert!(matches!(resources.render_pipelines[failed], PipelineState::Failed));
+        assert_ne!(resources.create_render_pipeline_async(desc(&uint), None).unwrap(), failed);
+    }
+}
@@ -0,0 +1,255 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use serde::Serialize;
+
+use crate::gpu::render_graph::types::{GraphError, Node, ResourceHandle};
+
+/// How `RenderGraph::compile` picks the next node among those whose dependencies are met.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Default, Debug)]
+pub enum SchedulingStrategy {
+    /// Ready nodes run in the order they were added.
+    #[default]
+    Submission,
+    /// Greedily runs the ready node that grows the live transient set the least (or shrinks it
+    /// the most). With `cluster_attachments`, nodes sharing an attachment with the previously
+    /// scheduled node are preferred first.
+    MinimizeMemory { cluster_attachments: bool },
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct ScheduleReport {
+    pub order: Vec<usize>,
+    // bytes of transients alive at once at the worst point of `order`; what aliasing could reach
+    pub peak_bytes: u64,
+    // bytes of all transients if none of them shared memory
+    pub total_bytes: u64,
+}
+
+/// Dependency edges (`edges[from]` lists nodes that must run after `from`).
+///
+/// A resource written by several nodes is versioned in submission order: a reader sees the last
+/// writer added before it (or the first writer, when it was declared before any), and the next
+/// writer waits for those readers.
+pub fn dependency_edges(nodes: &[Node]) -> Vec<Vec<usize>> {
+    let mut writers: HashMap<ResourceHandle, Vec<usize>> = HashMap::new();
+    for (idx, node) in nodes.iter().enumerate() {
+        for resource in node.writes() {
+            let resource_writers = writers.entry(resource).or_default();
+            if resource_writers.last() != Some(&idx) {
+                resource_writers.push(idx);
+            }
+        }
+    }
+
+    let mut edges: Vec<Vec<usize>> = vec![Vec::new(); nodes.len()];
+    let mut add_edge = |from: usize, to: usize| {
+        if from != to && !edges[from].contains(&to) {
+            edges[from].push(to);
+        }
+    };
+
+    for resource_writers in writers.values() {
+        for pair in resource_writers.windows(2) {
+            add_edge(pair[0], pair[1]);
+        }
+    }
+
+    for (reader_idx, node) in nodes.iter().enumerate() {
+        for input in node.inputs.iter() {
+            let Some(resource_writers) = writers.get(&input.resource) else { continue; };
+
+            let version = match resource_writers.iter().rposition(|&w| w < reader_idx) {
+                Some(version) => version,
+                // the node itself is the first writer, there is nothing to read yet
+                None if resource_writers[0] == reader_idx => continue,
+                None => 0,
+            };
+
+            add_edge(resource_writers[version], reader_idx);
+            if let Some(&next_writer) = resource_writers.get(version + 1) {
+                add_edge(reader_idx, next_writer);
+            }
+        }
+    }
+
+    edges
+}
+
+pub fn schedule(
+    nodes: &[Node],
+    strategy: SchedulingStrategy,
+    size_of: &dyn Fn(ResourceHandle) -> u64,
+) -> Result<ScheduleReport, GraphError> {
+    let edges = dependency_edges(nodes);
+
+    let mut dependency_count = vec![0; nodes.len()];
+    for targets in edges.iter() {
+        for &v in targets {
+            dependency_count[v] += 1;
+        }
+    }
+
+    let order = match strategy {
+        SchedulingStrategy::Submission => submission_order(&edges, dependency_count),
+        SchedulingStrategy::MinimizeMemory { cluster_attachments } => {
+            memory_order(nodes, &edges, dependency_count, cluster_attachments, size_of)
+        }
+    };
+
+    if order.len() != nodes.len() {
+        return Err(GraphError::Cycle);
+    }
+
+    let (peak_bytes, total_bytes) = estimate_memory(nodes, &order, size_of);
+
+    Ok(ScheduleReport {
+        order,
+        peak_bytes,
+        total_bytes,
+    })
+}
+
+/// Peak and total transient bytes for running `nodes` in `order`.
+pub fn estimate_memory(
+    nodes: &[Node],
+    order: &[usize],
+    size_of: &dyn Fn(ResourceHandle) -> u64,
+) -> (u64, u64) {
+    let mut last_use: HashMap<ResourceHandle, usize> = HashMap::new();
+    for (step, &idx) in order.iter().enumerate() {
+        for resource in nodes[idx].resources() {
+            last_use.insert(resource, step);
+        }
+    }
+
+    let mut live = HashSet::new();
+    let mut live_bytes = 0;
+    let mut peak_bytes = 0;
+
+    for (step, &idx) in order.iter().enumerate() {
+        for resource in nodes[idx].resources() {
+            if live.insert(resource) {
+                live_bytes += size_of(resource);
+            }
+        }
+        peak_bytes = peak_bytes.max(live_bytes);
+
+        for resource in unique_resources(&nodes[idx]) {
+            if last_use[&resource] == step {
+                live_bytes -= size_of(resource);
+            }
+        }
+    }
+
+    let total_bytes = last_use.keys().map(|&resource| size_of(resource)).sum();
+
+    (peak_bytes, total_bytes)
+}
+
+fn submission_order(edges: &[Vec<usize>], mut dependency_count: Vec<usize>) -> Vec<usize> {
+    let mut queue = VecDeque::new();
+    let mut order = Vec::new();
+
+    for (i, &count) in dependency_count.iter().enumerate() {
+        if count == 0 {
+            queue.push_back(i);
+        }
+    }
+
+    while let Some(u) = queue.pop_front() {
+        order.push(u);
+
+        for &v in &edges[u] {
+            dependency_count[v] -= 1;
+            if dependency_count[v] == 0 {
+                queue.push_back(v);
+            }
+        }
+    }
+
+    order
+}
+
+fn memory_order(
+    nodes: &[Node],
+    edges: &[Vec<usize>],
+    mut dependency_count: Vec<usize>,
+    cluster_attachments: bool,
+    size_of: &dyn Fn(ResourceHandle) -> u64,
+) -> Vec<usize> {
+    // number of not yet scheduled nodes touching each resource
+    let mut remaining_users: HashMap<ResourceHandle, usize> = HashMap::new();
+    for node in nodes.iter() {
+        for resource in unique_resources(node) {
+            *remaining_users.entry(resource).or_default() += 1;
+        }
+    }
+
+    let mut live: HashSet<ResourceHandle> = HashSet::new();
+    let mut ready: Vec<usize> = (0..nodes.len()).filter(|&i| dependency_count[i] == 0).collect();
+    let mut order = Vec::new();
+    let mut previous_attachments: Vec<ResourceHandle> = Vec::new();
+
+    while !ready.is_empty() {
+        let memory_delta = |idx: usize| -> i64 {
+            unique_resources(&nodes[idx])
+                .into_iter()
+                .map(|resource| {
+                    let size = size_of(resource) as i64;
+                    let allocated = if live.contains(&resource) { 0 } else { size };
+                    let freed = if remaining_users[&resource] == 1 { size } else { 0 };
+                    allocated - freed
+                })
+                .sum()
+        };
+        let shares_attachment = |idx: usize| {
+            cluster_attachments
+                && nodes[idx]
+                    .resources()
+                    .any(|resource| previous_attachments.contains(&resource))
+        };
+
+        // ties go to the node added first, which keeps the order stable
+        let (position, _) = ready
+            .iter()
+            .enumerate()
+            .min_by_key(|&(_, &idx)| (!shares_attachment(idx), memory_delta(idx), idx))
+            .expect("ready set is not empty");
+        let u = ready.swap_remove(position);
+
+        for resource in unique_resources(&nodes[u]) {
+            let users = remaining_users.get_mut(&resource).expect("resource user count");
+            *users -= 1;
+            if *users == 0 {
+                live.remove(&resource);
+            } else {
+                live.insert(resource);
+            }
+        }
+
+        previous_attachments = nodes[u]
+            .writes()
+            .filter(|resource| matches!(resource, ResourceHandle::Texture(_)))
+            .collect();
+        order.push(u);
+
+        for &v in &edges[u] {
+            dependency_count[v] -= 1;
+            if dependency_count[v] == 0 {
+                ready.push(v);
+            }
+        }
+    }
+
+    order
+}
+
+fn unique_resources(node: &Node) -> Vec<ResourceHandle> {
+    let mut resources: Vec<ResourceHandle> = Vec::new();
+    for resource in node.resources() {
+        if !resources.contains(&resource) {
+            resources.push(resource);
+        }
+    }
+    resources
+}
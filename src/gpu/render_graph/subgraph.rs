@@ -0,0 +1,58 @@
+use crate::gpu::render_graph::types::ResourceHandle;
+
+/// Anything a subgraph instance (see `RenderGraph::subgraph`) can return; lists the resources
+/// it exports to the caller.
+pub trait SubgraphOutputs {
+    fn for_each_resource(&self, f: &mut dyn FnMut(ResourceHandle));
+}
+
+impl SubgraphOutputs for () {
+    fn for_each_resource(&self, _f: &mut dyn FnMut(ResourceHandle)) {}
+}
+
+impl SubgraphOutputs for ResourceHandle {
+    fn for_each_resource(&self, f: &mut dyn FnMut(ResourceHandle)) {
+        f(*self);
+    }
+}
+
+impl<T: SubgraphOutputs> SubgraphOutputs for Option<T> {
+    fn for_each_resource(&self, f: &mut dyn FnMut(ResourceHandle)) {
+        if let Some(outputs) = self {
+            outputs.for_each_resource(f);
+        }
+    }
+}
+
+impl<T: SubgraphOutputs> SubgraphOutputs for Vec<T> {
+    fn for_each_resource(&self, f: &mut dyn FnMut(ResourceHandle)) {
+        for outputs in self {
+            outputs.for_each_resource(f);
+        }
+    }
+}
+
+impl<T: SubgraphOutputs, const N: usize> SubgraphOutputs for [T; N] {
+    fn for_each_resource(&self, f: &mut dyn FnMut(ResourceHandle)) {
+        for outputs in self {
+            outputs.for_each_resource(f);
+        }
+    }
+}
+
+macro_rules! impl_subgraph_outputs_tuple {
+    ($($name:ident),+) => {
+        impl<$($name: SubgraphOutputs),+> SubgraphOutputs for ($($name,)+) {
+            #[allow(non_snake_case)]
+            fn for_each_resource(&self, f: &mut dyn FnMut(ResourceHandle)) {
+                let ($($name,)+) = self;
+                $($name.for_each_resource(f);)+
+            }
+        }
+    };
+}
+
+impl_subgraph_outputs_tuple!(A);
+impl_subgraph_outputs_tuple!(A, B);
+impl_subgraph_outputs_tuple!(A, B, C);
+impl_subgraph_outputs_tuple!(A, B, C, D);
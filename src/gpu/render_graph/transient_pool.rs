@@ -0,0 +1,247 @@
+use std::collections::HashMap;
+
+use serde::Serialize;
+
+use crate::gpu::render_graph::types::{BufferDesc, TextureDesc};
+
+// frames a released resource is kept around before it is destroyed
+pub const DEFAULT_MAX_UNUSED_FRAMES: u32 = 8;
+
+pub struct PooledTexture {
+    pub texture: wgpu::Texture,
+    pub view: wgpu::TextureView,
+    desc: TextureDesc,
+    // label of the view, the texture keeps the one it was created with
+    label: String,
+}
+
+pub struct PooledBuffer {
+    pub buffer: wgpu::Buffer,
+    desc: BufferDesc,
+}
+
+/// Sizes of the pool; bytes are estimates from the descriptions.
+#[derive(Serialize, Clone, Copy, Default, Debug)]
+pub struct PoolStats {
+    pub textures_in_use: usize,
+    pub buffers_in_use: usize,
+    pub textures_free: usize,
+    pub buffers_free: usize,
+    pub bytes_in_use: u64,
+    pub bytes_free: u64,
+    // totals since the pool was created
+    pub created: u64,
+    pub evicted: u64,
+}
+
+/// Physical textures and buffers for graph transients, handed out by description. Released
+/// resources are reused by later requests for the same description, whatever their label, and
+/// destroyed once they went unused for `max_unused_frames` frames. wgpu cannot rename a texture
+/// or buffer, so a reused texture gets a view labelled for its new use and keeps its first label.
+pub struct TransientPool {
+    free_textures: HashMap<TextureDesc, Vec<(PooledTexture, u32)>>,
+    free_buffers: HashMap<BufferDesc, Vec<(PooledBuffer, u32)>>,
+    max_unused_frames: u32,
+    stats: PoolStats,
+}
+
+impl TransientPool {
+    pub fn new(max_unused_frames: u32) -> Self {
+        TransientPool {
+            free_textures: HashMap::new(),
+            free_buffers: HashMap::new(),
+            max_unused_frames,
+            stats: PoolStats::default(),
+        }
+    }
+
+    pub fn acquire_texture(&mut self, device: &wgpu::Device, desc: &TextureDesc, label: &str) -> PooledTexture {
+        self.stats.textures_in_use += 1;
+        self.stats.bytes_in_use += desc.size_in_bytes();
+
+        if let Some((mut texture, _)) = self.free_textures.get_mut(desc).and_then(Vec::pop) {
+            self.stats.textures_free -= 1;
+            self.stats.bytes_free -= desc.size_in_bytes();
+            if texture.label != label {
+                texture.view = create_view(&texture.texture, label);
+                texture.label = label.to_string();
+            }
+            return texture;
+        }
+
+        self.stats.created += 1;
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size: desc.size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: desc.dimension,
+            format: desc.format,
+            usage: desc.usage,
+            view_formats: &[],
+        });
+        let view = create_view(&texture, label);
+
+        PooledTexture { texture, view, desc: *desc, label: label.to_string() }
+    }
+
+    pub fn acquire_buffer(&mut self, device: &wgpu::Device, desc: &BufferDesc, label: &str) -> PooledBuffer {
+        self.stats.buffers_in_use += 1;
+        self.stats.bytes_in_use += desc.size_in_bytes();
+
+        // a reused buffer would not be mapped anymore
+        if !desc.mapped_at_creation
+            && let Some((buffer, _)) = self.free_buffers.get_mut(desc).and_then(Vec::pop)
+        {
+            self.stats.buffers_free -= 1;
+            self.stats.bytes_free -= desc.size_in_bytes();
+            return buffer;
+        }
+
+        self.stats.created += 1;
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(label),
+            size: desc.size,
+            usage: desc.usage,
+            mapped_at_creation: desc.mapped_at_creation,
+        });
+
+        PooledBuffer { buffer, desc: *desc }
+    }
+
+    pub fn release_texture(&mut self, texture: PooledTexture) {
+        let bytes = texture.desc.size_in_bytes();
+        self.stats.textures_in_use -= 1;
+        self.stats.bytes_in_use -= bytes;
+        self.stats.textures_free += 1;
+        self.stats.bytes_free += bytes;
+
+        self.free_textures.entry(texture.desc).or_default().push((texture, 0));
+    }
+
+    pub fn release_buffer(&mut self, buffer: PooledBuffer) {
+        let bytes = buffer.desc.size_in_bytes();
+        self.stats.buffers_in_use -= 1;
+        self.stats.bytes_in_use -= bytes;
+
+        if buffer.desc.mapped_at_creation {
+            return;
+        }
+        self.stats.buffers_free += 1;
+        self.stats.bytes_free += bytes;
+
+        self.free_buffers.entry(buffer.desc).or_default().push((buffer, 0));
+    }
+
+    /// Ages the free resources by one frame and destroys those unused for too long.
+    pub fn end_frame(&mut self) {
+        let max_unused_frames = self.max_unused_frames;
+        let stats = &mut self.stats;
+
+        for (desc, free) in self.free_textures.iter_mut() {
+            free.retain_mut(|(_, unused_frames)| {
+                *unused_frames += 1;
+                let keep = *unused_frames <= max_unused_frames;
+                if !keep {
+                    stats.textures_free -= 1;
+                    stats.bytes_free -= desc.size_in_bytes();
+                    stats.evicted += 1;
+                }
+                keep
+            });
+        }
+        self.free_textures.retain(|_, free| !free.is_empty());
+
+        for (desc, free) in self.free_buffers.iter_mut() {
+            free.retain_mut(|(_, unused_frames)| {
+                *unused_frames += 1;
+                let keep = *unused_frames <= max_unused_frames;
+                if !keep {
+                    stats.buffers_free -= 1;
+                    stats.bytes_free -= desc.size_in_bytes();
+                    stats.evicted += 1;
+                }
+                keep
+            });
+        }
+        self.free_buffers.retain(|_, free| !free.is_empty());
+    }
+
+    pub fn stats(&self) -> PoolStats {
+        self.stats
+    }
+}
+
+fn create_view(texture: &wgpu::Texture, label: &str) -> wgpu::TextureView {
+    texture.create_view(&wgpu::TextureViewDescriptor {
+        label: Some(&format!("{label} (view)")),
+        ..Default::default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gpu::context::test_device;
+
+    fn target() -> TextureDesc {
+        TextureDesc {
+            size: wgpu::Extent3d { width: 16, height: 16, depth_or_array_layers: 1 },
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8Unorm,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+        }
+    }
+
+    #[test]
+    #[ignore = "needs a GPU adapter"]
+    fn reuses_released_resources_with_the_same_description() {
+        let (device, _queue) = test_device();
+        let mut pool = TransientPool::new(DEFAULT_MAX_UNUSED_FRAMES);
+
+        let color = pool.acquire_texture(&device, &target(), "Color");
+        pool.release_texture(color);
+        pool.end_frame();
+
+        // other label, same description: reuses the texture under a relabelled view
+        let bloom = pool.acquire_texture(&device, &target(), "Bloom");
+        let stats = pool.stats();
+        assert_eq!(stats.created, 1);
+        assert_eq!((stats.textures_in_use, stats.textures_free), (1, 0));
+        assert_eq!(bloom.label, "Bloom");
+
+        let color = pool.acquire_texture(&device, &target(), "Color");
+        assert_eq!(pool.stats().created, 2);
+
+        pool.release_texture(color);
+        pool.release_texture(bloom);
+        let stats = pool.stats();
+        assert_eq!((stats.textures_in_use, stats.textures_free), (0, 2));
+        assert_eq!(stats.bytes_free, 2 * target().size_in_bytes());
+    }
+
+    #[test]
+    #[ignore = "needs a GPU adapter"]
+    fn evicts_resources_unused_for_too_long() {
+        let (device, _queue) = test_device();
+        let mut pool = TransientPool::new(2);
+
+        let buffer = BufferDesc { size: 256, usage: wgpu::BufferUsages::STORAGE, mapped_at_creation: false };
+        let color = pool.acquire_texture(&device, &target(), "Color");
+        let data = pool.acquire_buffer(&device, &buffer, "Data");
+        pool.release_texture(color);
+        pool.release_buffer(data);
+
+        pool.end_frame();
+        pool.end_frame();
+        assert_eq!(pool.stats().evicted, 0);
+
+        pool.end_frame();
+        let stats = pool.stats();
+        assert_eq!(stats.evicted, 2);
+        assert_eq!((stats.textures_free, stats.buffers_free, stats.bytes_free), (0, 0, 0));
+
+        pool.acquire_texture(&device, &target(), "Color");
+        assert_eq!(pool.stats().created, 3);
+    }
+}
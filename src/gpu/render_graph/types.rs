@@ -1,8 +1,9 @@
 use std::{
+    num::NonZeroU32,
     path::{Path, PathBuf},
-    sync::{Arc, Mutex},
 };
 
+use serde::Serialize;
 use slotmap::new_key_type;
 use wgpu::{BindGroupLayout, CommandEncoder};
 
@@ -15,6 +16,7 @@ new_key_type! {
     pub struct PipelineLayoutHandle;
 }
 
+#[derive(Serialize, Hash, PartialEq, Eq, Clone, Copy, Debug)]
 pub enum NodeType {
     RenderPass,
     ComputePass,
@@ -24,30 +26,69 @@ pub enum NodeType {
 pub struct Node {
     pub name: String,
     pub kind: NodeType,
+    // debug scope the node was added in, used for nested debug groups
+    pub scope: Option<usize>,
 
     pub inputs: Vec<NodeInput>,
     pub outputs: Vec<NodeOutput>,
     pub depth_texture: Option<ResourceHandle>,
+    // explicit `PassBuilder::bind` entries; each resource is also an input or output
+    pub bindings: Vec<NodeBinding>,
 
     pub pipeline: Option<PipelineHandle>,
+    // render passes only; attachments are loaded instead of cleared when set
+    pub viewport: Option<Viewport>,
+    // render passes only; views rendered at once into the array layers of the attachments
+    pub multiview_mask: Option<NonZeroU32>,
 
     pub execute: Option<Box<dyn FnOnce(PassContext<'_, '_>)>>,
+
+    // evaluated at compile time; disabled nodes are culled
+    pub condition: Option<Box<dyn Fn() -> bool>>,
+    // (output, replacement) read by downstream nodes while this node is disabled
+    pub fallbacks: Vec<(ResourceHandle, ResourceHandle)>,
+
+    // only set for transfer nodes
+    pub transfer: Option<TransferOps>,
+}
+
+impl Node {
+    /// Every resource the node reads or writes, including the depth attachment.
+    pub fn resources(&self) -> impl Iterator<Item = ResourceHandle> + '_ {
+        self.inputs
+            .iter()
+            .map(|input| input.resource)
+            .chain(self.writes())
+    }
+
+    pub fn writes(&self) -> impl Iterator<Item = ResourceHandle> + '_ {
+        self.outputs
+            .iter()
+            .map(|output| output.resource)
+            .chain(self.depth_texture)
+    }
 }
 
-#[derive(PartialEq, Eq, Hash, Clone, Copy)]
+#[derive(Serialize, PartialEq, Eq, Hash, Clone, Copy, Debug)]
 pub enum ResourceHandle {
     Buffer(BufferHandle),
     Texture(TextureHandle),
 }
 
-#[derive(Hash, PartialEq, Eq, Clone, Copy)]
+#[derive(Serialize, Hash, PartialEq, Eq, Clone, Copy, Debug)]
 pub struct BufferDesc {
     pub size: u64,
     pub usage: wgpu::BufferUsages,
     pub mapped_at_creation: bool,
 }
 
-#[derive(Hash, PartialEq, Eq, Clone, Copy)]
+impl BufferDesc {
+    pub fn size_in_bytes(&self) -> u64 {
+        self.size
+    }
+}
+
+#[derive(Serialize, Hash, PartialEq, Eq, Clone, Copy, Debug)]
 pub struct TextureDesc {
     pub size: wgpu::Extent3d,
     // mip_level_count: u32 = 1,
@@ -57,20 +98,63 @@ pub struct TextureDesc {
     pub usage: wgpu::TextureUsages,
     // view_formats: &'a [] = base is Rgba8SnormSrgb
 }
+
+impl TextureDesc {
+    /// Estimated footprint of the texture, ignoring driver padding.
+    pub fn size_in_bytes(&self) -> u64 {
+        let (block_width, block_height) = self.format.block_dimensions();
+        // combined depth-stencil formats have no single copy size
+        let block_size = self
+            .format
+            .block_copy_size(None)
+            .or(self.format.target_pixel_byte_cost())
+            .unwrap_or(4);
+
+        self.size.width.div_ceil(block_width) as u64
+            * self.size.height.div_ceil(block_height) as u64
+            * self.size.depth_or_array_layers as u64
+            * block_size as u64
+    }
+}
+/// Pixel rectangle of the attachments a render pass draws into; also used as its scissor.
+#[derive(Serialize, Hash, PartialEq, Eq, Clone, Copy, Debug)]
+pub struct Viewport {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Viewport {
+    pub fn new(x: u32, y: u32, width: u32, height: u32) -> Self {
+        Viewport { x, y, width, height }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
 pub struct NodeInput {
     pub binding: u32,
     pub resource: ResourceHandle,
 }
+#[derive(Clone, Copy, Debug)]
 pub struct NodeOutput {
     pub binding: u32,
     pub resource: ResourceHandle,
 }
+/// A resource bound at `@group(group) @binding(binding)` of a pass's pipeline.
+#[derive(Serialize, Hash, PartialEq, Eq, Clone, Copy, Debug)]
+pub struct NodeBinding {
+    pub group: u32,
+    pub binding: u32,
+    pub resource: ResourceHandle,
+}
 #[derive(Hash, PartialEq, Eq)]
 pub enum ResourceType {
     Buffer,
     Texture,
 }
 
+#[derive(Serialize, Hash, PartialEq, Eq, Clone, Copy, Debug)]
 pub enum PipelineHandle {
     Render(RenderPipelineHandle),
     Compute(ComputePipelineHandle),
@@ -82,13 +166,6 @@ pub struct UploadOp {
     pub data: Vec<u8>,
 }
 
-pub struct DownloadOp {
-    pub source: ResourceHandle,
-    pub offset: u64,
-    pub size: u64,
-    pub data: Vec<u8>,
-}
-
 pub struct CopyOp {
     pub src: ResourceHandle,
     pub dst: ResourceHandle,
@@ -97,20 +174,138 @@ pub struct CopyOp {
     pub dst_offset: u64,
 }
 
-pub struct ReadbackTicket<T> {
-    data: Arc<Mutex<Option<Vec<u8>>>>,
-    _marker: std::marker::PhantomData<T>,
+/// Upload of texel data into a texture region. `layout` describes `data`, it does not need to be
+/// 256-byte aligned; rows are repacked into an aligned staging buffer.
+pub struct TextureUploadOp {
+    pub target: ResourceHandle,
+    pub region: TextureRegion,
+    pub layout: wgpu::TexelCopyBufferLayout,
+    pub data: Vec<u8>,
 }
 
-impl<T: bytemuck::Pod> ReadbackTicket<T> {
-    pub fn try_get(&self) -> Option<T> {
-        if let Ok(lock) = self.data.try_lock() {
-            lock.as_ref().map(|bytes| *bytemuck::from_bytes::<T>(bytes))
-        } else {
-            None
+/// Copy of a texture region to/from a buffer or another texture. The buffer side uses rows
+/// padded to `padded_bytes_per_row`, starting at `buffer_offset`.
+pub struct TextureCopyOp {
+    pub src: ResourceHandle,
+    pub dst: ResourceHandle,
+    pub region: TextureRegion,
+    // destination of texture to texture copies, with the extent of `region`
+    pub dst_region: TextureRegion,
+    pub buffer_offset: u64,
+}
+
+#[derive(Clone, Copy)]
+pub struct TextureRegion {
+    pub mip_level: u32,
+    // first array layer, or depth slice for 3D textures
+    pub array_layer: u32,
+    pub origin: wgpu::Origin2d,
+    pub extent: wgpu::Extent3d,
+    pub aspect: wgpu::TextureAspect,
+}
+
+impl TextureRegion {
+    /// Region starting at texel 0 of mip 0, layer 0.
+    pub fn new(extent: wgpu::Extent3d) -> Self {
+        TextureRegion {
+            mip_level: 0,
+            array_layer: 0,
+            origin: wgpu::Origin2d::ZERO,
+            extent,
+            aspect: wgpu::TextureAspect::All,
+        }
+    }
+
+    pub fn array_layer(mut self, array_layer: u32) -> Self {
+        self.array_layer = array_layer;
+        self
+    }
+
+    pub fn origin(mut self, x: u32, y: u32) -> Self {
+        self.origin = wgpu::Origin2d { x, y };
+        self
+    }
+
+    pub fn origin_3d(&self) -> wgpu::Origin3d {
+        wgpu::Origin3d {
+            x: self.origin.x,
+            y: self.origin.y,
+            z: self.array_layer,
         }
     }
 }
+
+/// Bytes per row of a `width` texels wide copy, rounded up to `COPY_BYTES_PER_ROW_ALIGNMENT`.
+/// Buffers on the buffer side of texture copies must use this row pitch.
+pub fn padded_bytes_per_row(format: wgpu::TextureFormat, aspect: wgpu::TextureAspect, width: u32) -> u32 {
+    unpadded_bytes_per_row(format, aspect, width).next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT)
+}
+
+pub fn unpadded_bytes_per_row(format: wgpu::TextureFormat, aspect: wgpu::TextureAspect, width: u32) -> u32 {
+    let (block_width, _) = format.block_dimensions();
+    let block_size = format
+        .block_copy_size(Some(aspect))
+        .expect("Format/aspect combination cannot be copied");
+    width.div_ceil(block_width) * block_size
+}
+
+pub struct ClearBufferOp {
+    pub target: ResourceHandle,
+    pub offset: u64,
+    // None clears to the end of the buffer
+    pub size: Option<u64>,
+}
+
+/// Zeroes a texture range; uses `clear_texture` when the device has `CLEAR_TEXTURE`,
+/// otherwise a clearing render pass or a copy from a zeroed staging buffer.
+pub struct ClearTextureOp {
+    pub target: ResourceHandle,
+    pub range: wgpu::ImageSubresourceRange,
+}
+
+/// Operations recorded by a transfer node, executed in this order.
+pub struct TransferOps {
+    pub clear_buffer: Vec<ClearBufferOp>,
+    pub clear_texture: Vec<ClearTextureOp>,
+    pub upload: Vec<UploadOp>,
+    pub copy: Vec<CopyOp>,
+    pub texture_upload: Vec<TextureUploadOp>,
+    pub texture_copy: Vec<TextureCopyOp>,
+}
+
+#[derive(Debug)]
+pub enum GraphError {
+    Cycle,
+    // a node used a transient private to a subgraph instance it is not part of
+    ScopeViolation { node: String, resource: String },
+    // an attachment of a multiview pass does not have one array layer per view
+    MultiviewLayers { node: String, resource: String, layers: u32, views: u32 },
+    // the pass and its pipeline disagree on the multiview mask
+    MultiviewMismatch { node: String, pass: Option<NonZeroU32>, pipeline: Option<NonZeroU32> },
+}
+
+impl std::fmt::Display for GraphError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GraphError::Cycle => write!(f, "render graph contains a dependency cycle"),
+            GraphError::ScopeViolation { node, resource } => write!(
+                f,
+                "node \"{node}\" uses \"{resource}\", which is private to another subgraph"
+            ),
+            GraphError::MultiviewLayers { node, resource, layers, views } => write!(
+                f,
+                "node \"{node}\" renders {views} views into \"{resource}\", which has {layers} array layers"
+            ),
+            GraphError::MultiviewMismatch { node, pass, pipeline } => write!(
+                f,
+                "node \"{node}\" has multiview mask {pass:?} but its pipeline was created with {pipeline:?}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for GraphError {}
+
 pub enum PassContext<'a, 'b> {
     Render(&'b mut wgpu::RenderPass<'a>),
     Compute(&'b mut wgpu::ComputePass<'a>),
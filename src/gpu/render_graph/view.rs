@@ -0,0 +1,71 @@
+use bytemuck::{Pod, Zeroable};
+use glam::{Mat4, Vec3};
+
+use crate::gpu::render_graph::graph::RenderGraph;
+use crate::gpu::render_graph::types::{ResourceHandle, TextureRegion, Viewport};
+
+/// Per-view shader data, uploaded into the uniform buffer of every view.
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable, Debug)]
+pub struct ViewUniforms {
+    pub view: [[f32; 4]; 4],
+    pub projection: [[f32; 4]; 4],
+    pub view_projection: [[f32; 4]; 4],
+    // xyz, w = 1
+    pub position: [f32; 4],
+    // x, y, width, height in pixels
+    pub viewport: [f32; 4],
+}
+
+impl ViewUniforms {
+    pub fn new(view: Mat4, projection: Mat4, position: Vec3, viewport: Viewport) -> Self {
+        ViewUniforms {
+            view: view.to_cols_array_2d(),
+            projection: projection.to_cols_array_2d(),
+            view_projection: (projection * view).to_cols_array_2d(),
+            position: position.extend(1.0).to_array(),
+            viewport: [
+                viewport.x as f32,
+                viewport.y as f32,
+                viewport.width as f32,
+                viewport.height as f32,
+            ],
+        }
+    }
+}
+
+/// A camera rendered by `RenderGraph::add_views`: its uniforms and where it draws. Views
+/// sharing a `target` (split-screen, picture-in-picture) each draw into their `viewport`.
+pub struct View {
+    pub name: String,
+    pub uniforms: ViewUniforms,
+    pub target: ResourceHandle,
+    // None draws over the whole target
+    pub viewport: Option<Viewport>,
+}
+
+/// What the per-view build closure of `RenderGraph::add_views` gets.
+pub struct ViewContext<'a> {
+    pub index: usize,
+    pub view: &'a View,
+    // uniform buffer holding `view.uniforms`, written before the view's passes
+    pub uniforms: ResourceHandle,
+}
+
+/// Copies every view (array layer) of a multiview target next to each other into `preview`,
+/// e.g. both eyes of a stereo pass into a side-by-side image twice as wide as one eye.
+pub fn side_by_side(graph: &mut RenderGraph, name: &str, views: ResourceHandle, preview: ResourceHandle) {
+    let size = graph.texture_desc(views).expect("Multiview target must be a texture").size;
+    let view_extent = wgpu::Extent3d { width: size.width, height: size.height, depth_or_array_layers: 1 };
+
+    let mut transfer = graph.add_transfer(name);
+    for layer in 0..size.depth_or_array_layers {
+        transfer = transfer.copy_texture_to(
+            views,
+            TextureRegion::new(view_extent).array_layer(layer),
+            preview,
+            TextureRegion::new(view_extent).origin(layer * size.width, 0),
+        );
+    }
+    transfer.finish();
+}
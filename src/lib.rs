@@ -0,0 +1,3 @@
+// The engine's GPU side as a library, so benches can drive the render graph; the app lives in
+// the binary.
+pub mod gpu;
@@ -5,8 +5,9 @@ use crate::{
     user_app::app::App,
 };
 
+use wgpu_engine::gpu;
+
 mod core;
-mod gpu;
 mod user_app;
 
 fn main() -> Result<(), winit::error::EventLoopError> {
@@ -1,18 +1,49 @@
+use glam::{Mat4, Vec3};
+
+use crate::gpu::render_graph::types::Viewport;
+use crate::gpu::render_graph::view::ViewUniforms;
 
 pub struct Camera {
-    pos: Vec4,
-    dir: Vec4,
+    pub position: Vec3,
+    pub forward: Vec3,
+    pub up: Vec3,
+    // vertical field of view in radians
+    pub fov_y: f32,
+    pub near: f32,
+    pub far: f32,
 }
 
 impl Camera {
     pub fn new() -> Self {
         Camera {
-            pos: Vec4::new(0.0, 0.0, 0.0, 1.0),
-            dir: Vec4::new(0.0, 0.0 -1.0, 1.0),
+            position: Vec3::ZERO,
+            forward: Vec3::NEG_Z,
+            up: Vec3::Y,
+            fov_y: 60f32.to_radians(),
+            near: 0.1,
+            far: 100.0,
+        }
+    }
+
+    pub fn look_at(position: Vec3, target: Vec3) -> Self {
+        Camera {
+            position,
+            forward: (target - position).normalize(),
+            ..Camera::new()
         }
     }
 
-    fn rotate(&mut self) {
-        
+    pub fn view(&self) -> Mat4 {
+        Mat4::look_to_rh(self.position, self.forward, self.up)
     }
-}
\ No newline at end of file
+
+    pub fn projection(&self, aspect: f32) -> Mat4 {
+        Mat4::perspective_rh(self.fov_y, aspect, self.near, self.far)
+    }
+
+    /// Uniforms of this camera drawn into `viewport`, using its aspect ratio.
+    pub fn uniforms(&self, viewport: Viewport) -> ViewUniforms {
+        let aspect = viewport.width.max(1) as f32 / viewport.height.max(1) as f32;
+        ViewUniforms::new(self.view(), self.projection(aspect), self.position, viewport)
+    }
+}
@@ -0,0 +1,439 @@
+use std::num::NonZeroU32;
+
+use glam::Vec3;
+
+use crate::gpu::render_graph::feature::{FrameInputs, RenderFeature, SCENE_COLOR};
+use crate::gpu::render_graph::graph::RenderGraph;
+use crate::gpu::render_graph::resource_pool::{
+    ComputePipelineDesc, ComputeState, FragmentState, PipelineVariant, RenderPipelineDesc, Resources, VertexState,
+};
+use crate::gpu::render_graph::types::{
+    BufferDesc, ComputePipelineHandle, NodeType, PassContext, PipelineHandle, RenderPipelineHandle, ResourceHandle, ShaderHandle,
+    TextureDesc, TextureRegion, Viewport,
+};
+use crate::gpu::render_graph::view::{View, ViewUniforms, side_by_side};
+use crate::user_app::camera::Camera;
+
+const COLOR_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+// vertex colors of the triangle, read by the vertex shader
+const PALETTE: [[u8; 4]; 3] = [[255, 40, 40, 255], [40, 255, 40, 255], [40, 40, 255, 255]];
+// the right view is drawn dimmed, by a variant of the triangle pipeline
+const DIMMED: PipelineVariant = PipelineVariant { defines: &[], constants: &[("BRIGHTNESS", 0.5)] };
+// both eyes of the stereo preview, rendered in one multiview pass
+const EYES: u32 = 2;
+
+/// Draws the demo triangle split-screen, seen by two cameras, at the render size and
+/// publishes it as `SCENE_COLOR`. A compute pass wobbles its corners, every other four seconds,
+/// in front of a sky gradient. Where the device supports multiview, a stereo pair is rendered in one pass
+/// and shown side by side in the top-left corner.
+pub struct Scene {
+    // per view
+    pipelines: Option<[RenderPipelineHandle; 2]>,
+    sky: Option<RenderPipelineHandle>,
+    animate: Option<ComputePipelineHandle>,
+    // multiview triangle and the pipeline drawing its preview; None without multiview
+    stereo: Option<(RenderPipelineHandle, RenderPipelineHandle)>,
+    // seconds since the first frame
+    time: f32,
+    // left and right half of the screen
+    cameras: [Camera; 2],
+    // left and right eye of the stereo preview
+    eyes: [Camera; 2],
+}
+
+impl Scene {
+    pub fn new() -> Self {
+        Scene {
+            pipelines: None,
+            sky: None,
+            animate: None,
+            stereo: None,
+            time: 0.0,
+            cameras: [
+                Camera::look_at(Vec3::new(0.0, 0.0, 1.5), Vec3::ZERO),
+                Camera::look_at(Vec3::new(1.2, 0.6, 1.2), Vec3::ZERO),
+            ],
+            // eyes further apart than real ones, so the two halves differ visibly
+            eyes: [-0.15, 0.15].map(|x| Camera::look_at(Vec3::new(x, 0.0, 1.5), Vec3::new(x, 0.0, 0.0))),
+        }
+    }
+}
+
+impl RenderFeature for Scene {
+    fn name(&self) -> &str {
+        "Scene"
+    }
+
+    fn setup(&mut self, resources: &mut Resources) {
+        let shader = resources.load_shader_stages("base").unwrap_or_else(|error| panic!("{error}"));
+        let layout = resources
+            .create_reflected_layout(Some("Triangle"), &[shader.vertex, shader.fragment])
+            .unwrap_or_else(|error| panic!("{error}"));
+
+        let pipeline = resources.create_render_pipeline(RenderPipelineDesc {
+            label: Some("Triangle"),
+            layout,
+            vertex: VertexState {
+                module: shader.vertex,
+                entry_point: Some("main"),
+                buffers: &[],
+                constants: &[],
+            },
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: DEPTH_FORMAT,
+                depth_write_enabled: true,
+                depth_compare: wgpu::CompareFunction::Less,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            fragment: Some(FragmentState {
+                module: shader.fragment,
+                entry_point: Some("main"),
+                targets: &[Some(COLOR_FORMAT.into())],
+                constants: &[],
+            }),
+            multiview_mask: None,
+        });
+        let pipeline = pipeline.unwrap_or_else(|error| panic!("{error}"));
+        let dimmed = resources.render_pipeline_variant(pipeline, &DIMMED).unwrap_or_else(|error| panic!("{error}"));
+        self.pipelines = Some([pipeline, dimmed]);
+
+        // checked up front, a device without multiview already rejects the stereo shader
+        match resources.check_multiview(EYES, Some("Stereo")) {
+            Ok(()) => self.stereo = Some(create_stereo_pipelines(resources, shader.vertex, shader.fragment)),
+            Err(error) => println!("{error}; the stereo preview is off"),
+        }
+
+        let animate = resources.load_shader("animate").unwrap_or_else(|error| panic!("{error}"));
+        let layout = resources
+            .create_reflected_layout(Some("Animate"), &[animate])
+            .unwrap_or_else(|error| panic!("{error}"));
+        let rest = resources.create_compute_pipeline(ComputePipelineDesc {
+            label: Some("Rest"),
+            layout,
+            compute: ComputeState {
+                module: animate,
+                entry_point: Some("rest"),
+                constants: &[],
+            },
+        });
+        let rest = rest.unwrap_or_else(|error| panic!("{error}"));
+        // compiled in the background, the corners rest until it is ready
+        let pipeline = resources.create_compute_pipeline_async(
+            ComputePipelineDesc {
+                label: Some("Animate"),
+                layout,
+                compute: ComputeState {
+                    module: animate,
+                    entry_point: Some("animate"),
+                    constants: &[],
+                },
+            },
+            Some(rest),
+        );
+        self.animate = Some(pipeline.unwrap_or_else(|error| panic!("{error}")));
+
+        // without a fallback the pass is skipped until the pipeline is ready, leaving the
+        // color target cleared
+        let sky = resources.load_shader("sky").unwrap_or_else(|error| panic!("{error}"));
+        let layout = resources.add_pipeline_layout(wgpu::PipelineLayoutDescriptor {
+            label: Some("Sky"),
+            bind_group_layouts: &[],
+            immediate_size: 0,
+        });
+        let pipeline = resources.create_render_pipeline_async(
+            RenderPipelineDesc {
+                label: Some("Sky"),
+                layout,
+                vertex: VertexState {
+                    module: sky,
+                    entry_point: Some("vs_sky"),
+                    buffers: &[],
+                    constants: &[],
+                },
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+                fragment: Some(FragmentState {
+                    module: sky,
+                    entry_point: Some("fs_sky"),
+                    targets: &[Some(COLOR_FORMAT.into())],
+                    constants: &[],
+                }),
+                multiview_mask: None,
+            },
+            None,
+        );
+        self.sky = Some(pipeline.unwrap_or_else(|error| panic!("{error}")));
+    }
+
+    fn build(&mut self, graph: &mut RenderGraph, inputs: &FrameInputs) {
+        let (Some(pipelines), Some(sky), Some(animate)) = (self.pipelines, self.sky, self.animate) else { return; };
+        self.time += inputs.delta_time;
+
+        let (width, height) = (inputs.render_width, inputs.render_height);
+        let target = |format: wgpu::TextureFormat, usage: wgpu::TextureUsages| TextureDesc {
+            size: wgpu::Extent3d { width, height, depth_or_array_layers: 1 },
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage,
+        };
+        let color = graph.add_texture(
+            "Color",
+            target(COLOR_FORMAT, wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING),
+        );
+        let depth = graph.add_texture("Depth", target(DEPTH_FORMAT, wgpu::TextureUsages::RENDER_ATTACHMENT));
+        graph.blackboard_mut().insert(SCENE_COLOR, color);
+        graph.mark_output(color);
+
+        graph
+            .add_pass("Sky", NodeType::RenderPass)
+            .write(color)
+            .use_pipeline(PipelineHandle::Render(sky))
+            .execute(|ctx| {
+                if let PassContext::Render(pass) = ctx {
+                    pass.draw(0..3, 0..1);
+                }
+            });
+
+        let palette_extent = wgpu::Extent3d { width: PALETTE.len() as u32, height: 1, depth_or_array_layers: 1 };
+        let palette = graph.add_texture(
+            "Palette",
+            TextureDesc {
+                size: palette_extent,
+                dimension: wgpu::TextureDimension::D2,
+                format: wgpu::TextureFormat::Rgba8Unorm,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            },
+        );
+        graph
+            .add_transfer("Upload palette")
+            .write_texture(palette, TextureRegion::new(palette_extent), wgpu::TexelCopyBufferLayout::default(), &PALETTE)
+            .finish();
+
+        let time = graph.add_buffer(
+            "Time",
+            BufferDesc {
+                size: 4,
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            },
+        );
+        graph.add_transfer("Upload time").write(time, 0, vec![self.time]).finish();
+
+        let offsets = graph.add_buffer(
+            "Offsets",
+            BufferDesc {
+                size: 3 * 8,
+                usage: wgpu::BufferUsages::STORAGE,
+                mapped_at_creation: false,
+            },
+        );
+        // read instead of `offsets` while the corners rest; culled while they move
+        let still = graph.add_buffer(
+            "Still offsets",
+            BufferDesc {
+                size: 3 * 8,
+                usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            },
+        );
+        graph.add_transfer("Clear still offsets").fill_buffer(still, 0, [0.0f32; 2], 3).finish();
+
+        let moving = (self.time / 4.0) as u32 % 2 == 0;
+        graph
+            .add_pass("Animate", NodeType::ComputePass)
+            .write(offsets)
+            .bind(0, 0, time)
+            .bind(0, 1, offsets)
+            .use_pipeline(PipelineHandle::Compute(animate))
+            .enabled_if(move || moving)
+            .fallback(offsets, still)
+            .execute(|ctx| {
+                if let PassContext::Compute(pass) = ctx {
+                    pass.dispatch_workgroups(1, 1, 1);
+                }
+            });
+
+        let half = width / 2;
+        let views: Vec<View> = [("Left", 0, half), ("Right", half, width - half)]
+            .into_iter()
+            .zip(self.cameras.iter())
+            .map(|((name, x, width), camera)| {
+                let viewport = Viewport::new(x, 0, width, height);
+                View {
+                    name: name.into(),
+                    uniforms: camera.uniforms(viewport),
+                    target: color,
+                    viewport: Some(viewport),
+                }
+            })
+            .collect();
+
+        graph.add_views(&views, |graph, ctx| {
+            graph
+                .add_pass("Triangle", NodeType::RenderPass)
+                .write(ctx.view.target)
+                .write_depth(depth)
+                .bind(0, 0, ctx.uniforms)
+                .bind(1, 0, palette)
+                .bind(2, 0, offsets)
+                .use_pipeline(PipelineHandle::Render(pipelines[ctx.index]))
+                .execute(|ctx| {
+                    if let PassContext::Render(pass) = ctx {
+                        pass.draw(0..3, 0..1);
+                    }
+                });
+        });
+
+        self.build_stereo(graph, color, palette, offsets, (width, height));
+    }
+}
+
+impl Scene {
+    // Both eyes at a quarter of the render size into the layers of one texture, copied side by
+    // side into a preview that is drawn over the top-left corner of `color`.
+    fn build_stereo(
+        &self,
+        graph: &mut RenderGraph,
+        color: ResourceHandle,
+        palette: ResourceHandle,
+        offsets: ResourceHandle,
+        (width, height): (u32, u32),
+    ) {
+        let Some((stereo, inset)) = self.stereo else { return; };
+        let (eye_width, eye_height) = ((width / 4).max(1), (height / 4).max(1));
+        let eye_target = |format: wgpu::TextureFormat, usage: wgpu::TextureUsages| TextureDesc {
+            size: wgpu::Extent3d { width: eye_width, height: eye_height, depth_or_array_layers: EYES },
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage,
+        };
+        let eyes = graph.add_texture(
+            "Eyes",
+            eye_target(COLOR_FORMAT, wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC),
+        );
+        let eye_depth = graph.add_texture(
+            "Eye depth",
+            eye_target(DEPTH_FORMAT, wgpu::TextureUsages::RENDER_ATTACHMENT),
+        );
+        let preview = graph.add_texture(
+            "Stereo preview",
+            TextureDesc {
+                size: wgpu::Extent3d { width: EYES * eye_width, height: eye_height, depth_or_array_layers: 1 },
+                dimension: wgpu::TextureDimension::D2,
+                format: COLOR_FORMAT,
+                usage: wgpu::TextureUsages::COPY_DST | wgpu::TextureUsages::TEXTURE_BINDING,
+            },
+        );
+
+        let eye_uniforms = graph.add_buffer(
+            "Eye uniforms",
+            BufferDesc {
+                size: (EYES as usize * size_of::<ViewUniforms>()) as u64,
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            },
+        );
+        let eye_viewport = Viewport::new(0, 0, eye_width, eye_height);
+        let uniforms = self.eyes.iter().map(|eye| eye.uniforms(eye_viewport)).collect();
+        graph.add_transfer("Upload eye uniforms").write(eye_uniforms, 0, uniforms).finish();
+
+        graph
+            .add_pass("Stereo", NodeType::RenderPass)
+            .write(eyes)
+            .write_depth(eye_depth)
+            .bind(0, 0, eye_uniforms)
+            .bind(1, 0, palette)
+            .bind(2, 0, offsets)
+            .multiview(EYES)
+            .use_pipeline(PipelineHandle::Render(stereo))
+            .execute(|ctx| {
+                if let PassContext::Render(pass) = ctx {
+                    pass.draw(0..3, 0..1);
+                }
+            });
+        side_by_side(graph, "Stereo side by side", eyes, preview);
+
+        graph
+            .add_pass("Stereo inset", NodeType::RenderPass)
+            .write(color)
+            .bind(0, 0, preview)
+            .viewport(Viewport::new(0, 0, EYES * eye_width, eye_height))
+            .use_pipeline(PipelineHandle::Render(inset))
+            .execute(|ctx| {
+                if let PassContext::Render(pass) = ctx {
+                    pass.draw(0..3, 0..1);
+                }
+            });
+    }
+}
+
+// The triangle pipeline drawing both eyes at once, from the `MULTIVIEW` variant of its vertex
+// shader, and the pipeline drawing the side-by-side preview.
+fn create_stereo_pipelines(
+    resources: &mut Resources,
+    vertex: ShaderHandle,
+    fragment: ShaderHandle,
+) -> (RenderPipelineHandle, RenderPipelineHandle) {
+    let vertex = resources.shader_variant(vertex, &["MULTIVIEW"]).unwrap_or_else(|error| panic!("{error}"));
+    let layout = resources
+        .create_reflected_layout(Some("Stereo"), &[vertex, fragment])
+        .unwrap_or_else(|error| panic!("{error}"));
+    let stereo = resources.create_render_pipeline(RenderPipelineDesc {
+        label: Some("Stereo"),
+        layout,
+        vertex: VertexState {
+            module: vertex,
+            entry_point: Some("main"),
+            buffers: &[],
+            constants: &[],
+        },
+        primitive: wgpu::PrimitiveState::default(),
+        depth_stencil: Some(wgpu::DepthStencilState {
+            format: DEPTH_FORMAT,
+            depth_write_enabled: true,
+            depth_compare: wgpu::CompareFunction::Less,
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState::default(),
+        }),
+        multisample: wgpu::MultisampleState::default(),
+        fragment: Some(FragmentState {
+            module: fragment,
+            entry_point: Some("main"),
+            targets: &[Some(COLOR_FORMAT.into())],
+            constants: &[],
+        }),
+        multiview_mask: NonZeroU32::new((1 << EYES) - 1),
+    });
+    let stereo = stereo.unwrap_or_else(|error| panic!("{error}"));
+
+    let shader = resources.load_shader("stereo_inset").unwrap_or_else(|error| panic!("{error}"));
+    let layout = resources
+        .create_reflected_layout(Some("Stereo inset"), &[shader])
+        .unwrap_or_else(|error| panic!("{error}"));
+    let inset = resources.create_render_pipeline(RenderPipelineDesc {
+        label: Some("Stereo inset"),
+        layout,
+        vertex: VertexState {
+            module: shader,
+            entry_point: Some("vs_inset"),
+            buffers: &[],
+            constants: &[],
+        },
+        primitive: wgpu::PrimitiveState::default(),
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState::default(),
+        fragment: Some(FragmentState {
+            module: shader,
+            entry_point: Some("fs_inset"),
+            targets: &[Some(COLOR_FORMAT.into())],
+            constants: &[],
+        }),
+        multiview_mask: None,
+    });
+    (stereo, inset.unwrap_or_else(|error| panic!("{error}")))
+}